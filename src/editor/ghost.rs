@@ -0,0 +1,116 @@
+/*!
+Translucent preview of where the [Hand] element would get placed.
+*/
+
+use bevy::{
+	pbr::NotShadowCaster,
+	picking::backend::HitData,
+	prelude::*,
+};
+
+use super::*;
+
+
+///Marks the preview entity
+#[derive(Component)]
+pub struct Ghost;
+
+///The materials of the [Ghost]
+#[derive(Resource)]
+pub struct GhostMaterials {
+	pub valid: Handle<StandardMaterial>,
+	///Used when the object can't be placed there
+	pub invalid: Handle<StandardMaterial>,
+}
+
+impl FromWorld for GhostMaterials {
+	fn from_world(world: &mut World) -> Self {
+		let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+		let mut translucent = |color: Color| materials.add(StandardMaterial {
+			base_color: color,
+			alpha_mode: AlphaMode::Blend,
+			unlit: true,
+			..default()
+		});
+		Self {
+			valid: translucent(Color::srgba(0.9, 0.95, 1., 0.4)),
+			invalid: translucent(Color::srgba(1., 0.15, 0.1, 0.5)),
+		}
+	}
+}
+
+///The object the pointer is over
+#[derive(Resource, Default)]
+pub struct Hovered(pub Option<(Entity, HitData)>);
+
+
+pub fn setup_ghost(
+	mut cmds: Commands,
+	root: Res<EditorRoot>,
+	materials: Res<GhostMaterials>,
+) {
+	cmds.spawn((
+		Ghost,
+		Mesh3d::default(),
+		MeshMaterial3d(materials.valid.clone()),
+		Transform::default(),
+		Visibility::Hidden,
+		NotShadowCaster,
+		// otherwise it would block clicking the object behind it
+		PickingBehavior::IGNORE,
+		Name::new("Placement Ghost"),
+	)).set_parent(root.0);
+}
+
+pub fn track_hover(
+	mut moves: EventReader<Pointer<Move>>,
+	mut outs: EventReader<Pointer<Out>>,
+	mut hovered: ResMut<Hovered>,
+) {
+	for out in outs.read() {
+		if hovered.0.as_ref().is_some_and(|(entity, _)| *entity == out.target) {
+			hovered.0 = None;
+		}
+	}
+	if let Some(last) = moves.read().last() {
+		hovered.0 = Some((last.target, last.hit.clone()));
+	}
+}
+
+pub fn update_ghost(
+	hovered: Res<Hovered>,
+	objects: Query<(&object::Pos, &element::Component, &orientation::Orientation)>,
+	hand: Option<Res<Hand>>,
+	hand_orientation: Res<HandOrientation>,
+	tool: Res<Tool>,
+	grid: Res<grid::Occupancy>,
+	materials: Res<GhostMaterials>,
+	mut ghosts: Query<(&mut Mesh3d, &mut MeshMaterial3d<StandardMaterial>, &mut Transform, &mut Visibility), With<Ghost>>,
+) {
+	let Ok((mut mesh, mut material, mut transform, mut visibility)) = ghosts.get_single_mut() else {return};
+	
+	let placement = hand.filter(|_| *tool == Tool::Place || tool.is_shape()).and_then(|hand| {
+		let (entity, hit) = hovered.0.as_ref()?;
+		// the hovered object might have been removed already
+		let clicked = objects.get(*entity).ok()?;
+		let pos = input::hand_placement(hit, clicked, &hand, &hand_orientation)?;
+		Some((hand, pos))
+	});
+	let Some((hand, pos)) = placement else {
+		*visibility = Visibility::Hidden;
+		return;
+	};
+	
+	let size = hand_orientation.0.rotate_size(hand.0.size);
+	let valid = grid.is_free(pos, size, None) && grid.touches(pos, size, None);
+	let new_material = if valid {&materials.valid} else {&materials.invalid};
+	
+	if mesh.0 != hand.0.graphics.mesh {
+		mesh.0 = hand.0.graphics.mesh.clone();
+	}
+	if material.0 != *new_material {
+		material.0 = new_material.clone();
+	}
+	*transform = object::Pos(pos).transform(&hand.0, hand_orientation.0);
+	*visibility = Visibility::Inherited;
+}
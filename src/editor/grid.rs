@@ -0,0 +1,110 @@
+/*!
+Spatial index of which objects occupy which grid cells.
+
+The [object::event] handlers keep it up to date immediately, instead of through [Commands],
+so events applied in the same frame see each others changes.
+*/
+
+use bevy::{
+	prelude::*,
+	utils::HashMap,
+};
+
+use super::*;
+
+
+///All the cells covered by an object at `pos` with the given `size`
+pub fn cells(pos: IVec3, size: IVec3) -> impl Iterator<Item = IVec3> {
+	(0..size.x).flat_map(move |x|
+		(0..size.y).flat_map(move |y|
+			(0..size.z).map(move |z| pos + IVec3::new(x, y, z))
+		)
+	)
+}
+
+
+///An object as stored in the [Occupancy]
+#[derive(Clone)]
+pub struct Placed {
+	pub pos: IVec3,
+	pub element: element::Ref,
+	pub orientation: orientation::Orientation,
+}
+
+impl Placed {
+	///Size of the object after rotating
+	pub fn size(&self) -> IVec3 {
+		self.orientation.rotate_size(self.element.size)
+	}
+	
+	pub fn cells(&self) -> impl Iterator<Item = IVec3> {
+		cells(self.pos, self.size())
+	}
+}
+
+
+///Which object occupies which cell
+#[derive(Resource, Default)]
+pub struct Occupancy {
+	cells: HashMap<IVec3, Entity>,
+	objects: HashMap<Entity, Placed>,
+}
+
+impl Occupancy {
+	///The object covering `cell`
+	pub fn get(&self, cell: IVec3) -> Option<Entity> {
+		self.cells.get(&cell).copied()
+	}
+	
+	pub fn object(&self, entity: Entity) -> Option<&Placed> {
+		self.objects.get(&entity)
+	}
+	
+	///The object covering `cell`, with it's info
+	pub fn object_at(&self, cell: IVec3) -> Option<(Entity, &Placed)> {
+		let entity = self.get(cell)?;
+		Some((entity, self.object(entity)?))
+	}
+	
+	///Whether an object at `pos` with the given `size` would fit, ignoring the `ignore` object
+	pub fn is_free(&self, pos: IVec3, size: IVec3, ignore: Option<Entity>) -> bool {
+		cells(pos, size)
+			.filter_map(|cell| self.get(cell))
+			.all(|entity| Some(entity) == ignore)
+	}
+	
+	///Whether an object at `pos` with the given `size` would share a face with another object, ignoring the `ignore` object
+	pub fn touches(&self, pos: IVec3, size: IVec3, ignore: Option<Entity>) -> bool {
+		let min = pos - IVec3::ONE;
+		let max = pos + size;
+		cells(min, size + IVec3::splat(2))
+			// skip the edges and corners, they only touch diagonally
+			.filter(|cell| {
+				let outside = cell.cmplt(pos) | cell.cmpeq(max);
+				outside.bitmask().count_ones() == 1
+			})
+			.filter_map(|cell| self.get(cell))
+			.any(|entity| Some(entity) != ignore)
+	}
+	
+	///Doesn't check for overlap, that should be done beforehand
+	pub fn insert(&mut self, entity: Entity, placed: Placed) {
+		for cell in placed.cells() {
+			self.cells.insert(cell, entity);
+		}
+		self.objects.insert(entity, placed);
+	}
+	
+	pub fn remove(&mut self, entity: Entity) -> Option<Placed> {
+		let placed = self.objects.remove(&entity)?;
+		for cell in placed.cells() {
+			self.cells.remove(&cell);
+		}
+		Some(placed)
+	}
+	
+	pub fn clear(&mut self) {
+		self.cells.clear();
+		self.objects.clear();
+	}
+}
@@ -0,0 +1,268 @@
+/*!
+Undo/redo history of changes to the creation.
+
+The [object::event] handlers record everything they change.
+All changes recorded within a frame form a single undo step, so things like placing multiple objects at once can be undone at once.
+Undoing sends the events for the inverse changes, which get recorded as the step to redo.
+*/
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::*;
+
+
+///A change that has been applied to the creation
+#[derive(Clone)]
+pub enum Action {
+	Create {
+		pos: IVec3,
+		element: element::Ref,
+		orientation: orientation::Orientation,
+	},
+	Delete {
+		pos: IVec3,
+		element: element::Ref,
+		orientation: orientation::Orientation,
+	},
+	Move {
+		from: IVec3,
+		to: IVec3,
+	},
+	Replace {
+		pos: IVec3,
+		old: element::Ref,
+		new: element::Ref,
+	},
+}
+
+impl Action {
+	///The action that undoes this one
+	pub fn inverse(&self) -> Self {
+		match self.clone() {
+			Self::Create {pos, element, orientation} => Self::Delete {pos, element, orientation},
+			Self::Delete {pos, element, orientation} => Self::Create {pos, element, orientation},
+			Self::Move {from, to} => Self::Move {from: to, to: from},
+			Self::Replace {pos, old, new} => Self::Replace {pos, old: new, new: old},
+		}
+	}
+
+	///Sends the event that applies this action
+	pub fn send(&self, writers: &mut object::event::Writers) {
+		match self.clone() {
+			Self::Create {pos, element, orientation} => {
+				writers.create.send(object::event::Create {pos: pos.into(), element, orientation});
+			},
+			Self::Delete {pos, ..} => {
+				writers.delete.send(object::event::Delete {pos: pos.into()});
+			},
+			Self::Move {from, to} => {
+				writers.moves.send(object::event::Move {from: from.into(), to: to.into()});
+			},
+			Self::Replace {pos, new, ..} => {
+				writers.replace.send(object::event::Replace {pos: pos.into(), element: new});
+			},
+		}
+	}
+}
+
+impl std::fmt::Display for Action {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Create {pos, element, ..} => write!(f, "Create {} at {pos}", element.name),
+			Self::Delete {pos, element, ..} => write!(f, "Delete {} at {pos}", element.name),
+			Self::Move {from, to} => write!(f, "Move {from} to {to}"),
+			Self::Replace {pos, old, new} => write!(f, "Replace {} with {} at {pos}", old.name, new.name),
+		}
+	}
+}
+
+
+///What the changes recorded this frame are caused by
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+enum Mode {
+	///Normal editing
+	#[default]
+	Record,
+	Undo,
+	Redo,
+	///Changes that shouldn't be undoable, like loading a creation
+	Ignore,
+}
+
+///A group of actions that get undone together
+pub type Step = Vec<Action>;
+
+
+#[derive(Resource)]
+pub struct History {
+	undo: VecDeque<Step>,
+	redo: Vec<Step>,
+	///Actions recorded this frame
+	pending: Vec<Action>,
+	mode: Mode,
+	///Maximum amount of steps that can be undone
+	pub limit: usize,
+}
+
+impl Default for History {
+	fn default() -> Self {
+		Self {
+			undo: VecDeque::new(),
+			redo: Vec::new(),
+			pending: Vec::new(),
+			mode: Mode::default(),
+			limit: 100,
+		}
+	}
+}
+
+impl History {
+	///Should be called for every change applied to the creation
+	pub fn record(&mut self, action: Action) {
+		self.pending.push(action);
+	}
+
+	///Don't record the changes made this frame
+	pub fn ignore_frame(&mut self) {
+		self.mode = Mode::Ignore;
+	}
+
+	pub fn clear(&mut self) {
+		self.undo.clear();
+		self.redo.clear();
+	}
+
+	pub fn can_undo(&self) -> bool {
+		!self.undo.is_empty()
+	}
+
+	pub fn can_redo(&self) -> bool {
+		!self.redo.is_empty()
+	}
+
+	///Steps that can be undone, most recent last
+	pub fn undo_steps(&self) -> impl DoubleEndedIterator<Item = &Step> {
+		self.undo.iter()
+	}
+
+	///Steps that can be redone, most recent last
+	pub fn redo_steps(&self) -> impl DoubleEndedIterator<Item = &Step> {
+		self.redo.iter()
+	}
+
+	pub fn undo(&mut self, writers: &mut object::event::Writers) {
+		let Some(step) = self.undo.pop_back() else {return};
+		Self::send_inverse(&step, writers);
+		self.mode = Mode::Undo;
+	}
+
+	pub fn redo(&mut self, writers: &mut object::event::Writers) {
+		let Some(step) = self.redo.pop() else {return};
+		Self::send_inverse(&step, writers);
+		self.mode = Mode::Redo;
+	}
+
+	fn send_inverse(step: &Step, writers: &mut object::event::Writers) {
+		for action in step.iter().rev() {
+			action.inverse().send(writers);
+		}
+	}
+
+	///Turns the actions recorded this frame into a step
+	fn commit(&mut self) {
+		let mode = std::mem::take(&mut self.mode);
+		if self.pending.is_empty() {return}
+		let step = std::mem::take(&mut self.pending);
+
+		match mode {
+			Mode::Record => {
+				self.redo.clear();
+				self.undo.push_back(step);
+			},
+			Mode::Undo => self.redo.push(step),
+			Mode::Redo => self.undo.push_back(step),
+			Mode::Ignore => {},
+		}
+
+		while self.undo.len() > self.limit {
+			self.undo.pop_front();
+		}
+	}
+}
+
+
+pub fn commit(
+	mut history: ResMut<History>,
+) {
+	history.commit();
+}
+
+
+fn control_pressed(keys: &ButtonInput<KeyCode>) -> bool {
+	keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+}
+
+pub fn undo_redo_input(
+	keys: Res<ButtonInput<KeyCode>>,
+	mut history: ResMut<History>,
+	mut writers: object::event::Writers,
+) {
+	if !control_pressed(&keys) {return}
+	let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+	if keys.just_pressed(KeyCode::KeyZ) {
+		if shift {
+			history.redo(&mut writers);
+		} else {
+			history.undo(&mut writers);
+		}
+	} else if keys.just_pressed(KeyCode::KeyY) {
+		history.redo(&mut writers);
+	}
+}
+
+
+fn step_label(step: &Step) -> String {
+	match step.as_slice() {
+		[] => "Nothing".into(),
+		[action] => action.to_string(),
+		[first, rest @ ..] => format!("{first} and {} more", rest.len()),
+	}
+}
+
+pub fn history_ui(
+	mut contexts: bevy_egui::EguiContexts,
+	mut history: ResMut<History>,
+	mut writers: object::event::Writers,
+) {
+	use bevy_egui::egui;
+	let Some(ctx) = contexts.try_ctx_mut() else {
+		// Primary window is missing, because it still is being initialized or has been closed
+		// This system can still run in those conditions, so just do nothing until other systems fix it
+		return;
+	};
+
+	egui::Window::new("History").resizable(true).default_open(false).show(ctx, |ui| {
+		ui.horizontal(|ui| {
+			if ui.add_enabled(history.can_undo(), egui::Button::new("Undo")).on_hover_text("Ctrl+Z").clicked() {
+				history.undo(&mut writers);
+			}
+			if ui.add_enabled(history.can_redo(), egui::Button::new("Redo")).on_hover_text("Ctrl+Shift+Z").clicked() {
+				history.redo(&mut writers);
+			}
+		});
+		ui.add(egui::Slider::new(&mut history.limit, 1..=1000).text("Limit"));
+
+		ui.separator();
+		egui::ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+			for step in history.redo_steps() {
+				ui.weak(step_label(step));
+			}
+			for step in history.undo_steps().rev() {
+				ui.label(step_label(step));
+			}
+		});
+	});
+}
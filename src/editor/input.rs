@@ -52,34 +52,159 @@ pub fn move_camera(
 }
 
 
+///The axis-aligned direction a face with `hit_normal` faces.
+/// The normal isn't axis aligned for round elements, so this takes the closest axis.
+pub fn face_normal(hit_normal: Vec3) -> Option<IVec3> {
+	let abs = hit_normal.abs();
+	let axis = if abs.x >= abs.y && abs.x >= abs.z {
+		0
+	} else if abs.y >= abs.z {
+		1
+	} else {
+		2
+	};
+	if hit_normal[axis] == 0. {return None}
+	
+	let mut normal = IVec3::ZERO;
+	normal[axis] = hit_normal[axis].signum() as i32;
+	Some(normal)
+}
+
+
+/**
+Where an object of `size` should be placed when clicking an object at `clicked_pos` with `clicked_size`.
+
+The new object is placed against the face that got hit, and lines up with the hit cell along that face.
+*/
+pub fn placement_pos(
+	clicked_pos: IVec3,
+	clicked_size: IVec3,
+	hit_position: Vec3,
+	hit_normal: Vec3,
+	size: IVec3,
+) -> Option<IVec3> {
+	let normal = face_normal(hit_normal)?;
+	let axis = (0..3).find(|axis| normal[*axis] != 0)?;
+	
+	let max = clicked_pos + clicked_size - IVec3::ONE;
+	let mut pos = hit_position.floor().as_ivec3().clamp(clicked_pos, max);
+	pos[axis] = if normal[axis] > 0 {
+		max[axis] + 1
+	} else {
+		clicked_pos[axis] - size[axis]
+	};
+	
+	Some(pos)
+}
+
+
+///Rotates the [Hand] element: R to yaw (around Y), T to pitch (around Z), G to roll (around X). Holding shift turns the other way
+pub fn rotate_hand(
+	keys: Res<ButtonInput<KeyCode>>,
+	mut hand_orientation: ResMut<HandOrientation>,
+) {
+	let sign = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {-1} else {1};
+	
+	for (key, axis) in [
+		(KeyCode::KeyR, IVec3::Y),
+		(KeyCode::KeyT, IVec3::Z),
+		(KeyCode::KeyG, IVec3::X),
+	] {
+		if keys.just_pressed(key) {
+			hand_orientation.0 = hand_orientation.0.rotated(axis * sign);
+		}
+	}
+}
+
+
+///Where the [Hand] element should be placed when pointing at `hit` on an object
+pub fn hand_placement(
+	hit: &bevy::picking::backend::HitData,
+	(clicked_pos, clicked_elemc, clicked_orientation): (&object::Pos, &element::Component, &orientation::Orientation),
+	hand: &Hand,
+	hand_orientation: &HandOrientation,
+) -> Option<IVec3> {
+	let (Some(hit_position), Some(hit_normal)) = (hit.position, hit.normal) else {return None};
+	let clicked_size = clicked_orientation.rotate_size(clicked_elemc.0.size);
+	let size = hand_orientation.0.rotate_size(hand.0.size);
+	placement_pos(clicked_pos.0, clicked_size, hit_position, hit_normal, size)
+}
+
+
 pub fn click_handler(
 	mut clicks: EventReader<Pointer<Click>>,
-	pos: Query<&object::Pos>,
-	mut create: EventWriter<object::event::Create>,
+	objects: Query<(&object::Pos, &element::Component, &orientation::Orientation)>,
+	mut writers: object::event::Writers,
 	selem: Res<Hand>,
+	hand_orientation: Res<HandOrientation>,
+	tool: Res<Tool>,
+	symmetry: Res<symmetry::Symmetry>,
+	grid: Res<grid::Occupancy>,
 ) {
 	for click in clicks.read() {
+		// the other buttons are used for the camera
+		if click.button != PointerButton::Primary {continue}
 		let ent = click.target;
-		let Ok(old_pos) = pos.get(ent) else {continue};
-		let Some(hit_normal) = click.hit.normal else {continue};
-		let offset = hit_normal.as_ivec3();
-		if offset == IVec3::ZERO {continue}
-		let pos = old_pos.0 + offset;
+		let Ok(clicked) = objects.get(ent) else {continue};
+		let (old_pos, elemc, old_orientation) = clicked;
 		
-		create.send(object::event::Create {
-			pos: pos.into(),
-			element: selem.0.clone(),
-		});
+		if *tool == Tool::Place {
+			let Some(pos) = hand_placement(&click.hit, clicked, &selem, &hand_orientation) else {continue};
+			let size = hand_orientation.0.rotate_size(selem.0.size);
+			
+			for (pos, orientation) in symmetry.images(pos, size, hand_orientation.0) {
+				writers.create.send(object::event::Create {
+					pos: pos.into(),
+					element: selem.0.clone(),
+					orientation,
+				});
+			}
+			continue;
+		}
+		
+		// the clicked object and whatever objects are at its mirror images
+		let size = old_orientation.rotate_size(elemc.0.size);
+		let mut targets = Vec::new();
+		for (pos, _) in symmetry.images(old_pos.0, size, *old_orientation) {
+			let Some((entity, placed)) = grid.object_at(pos) else {continue};
+			if targets.iter().any(|(other, _)| *other == entity) {continue}
+			targets.push((entity, placed));
+		}
+		
+		for (_, placed) in targets {
+			match *tool {
+				// handled above
+				Tool::Place => {},
+				Tool::Select | Tool::Line | Tool::Fill | Tool::HollowBox => {},
+				Tool::Remove => {
+					writers.delete.send(object::event::Delete {
+						pos: placed.pos.into(),
+					});
+				},
+				Tool::Replace => {
+					if placed.element.id == selem.0.id {continue}
+					writers.replace.send(object::event::Replace {
+						pos: placed.pos.into(),
+						element: selem.0.clone(),
+					});
+				},
+			}
+		}
 	}
 }
 
 
+///Marks the camera of the editor
+#[derive(Component)]
+pub struct EditorCamera;
+
 pub fn setup_camera(
 	mut cmds: Commands,
 	root: Res<EditorRoot>,
 ) {
 	cmds.spawn((
 		Camera3d::default(),
+		EditorCamera,
 		PerspectiveProjection {
 			fov: 80.,
 			..default()
@@ -14,29 +14,69 @@ impl CreationData {
 			objects: Vec::new(),
 		}
 	}
+	
+	pub fn from_objects<'a>(objects: impl IntoIterator<Item = (&'a object::Pos, &'a element::Component, &'a orientation::Orientation)>) -> Self {
+		let mut sv = Self::new();
+		
+		for (pos, elemc, orientation) in objects {
+			sv.objects.push(object::Object {
+				element: elemc.0.clone(),
+				pos: pos.clone(),
+				orientation: *orientation,
+			})
+		}
+		
+		sv
+	}
+	
+	///The same creation with its objects sorted by position, so it doesn't depend on the order they were placed in
+	pub fn canonical(&self) -> Self {
+		let mut objects = self.objects.clone();
+		// objects can't overlap, the element id is only there to be safe
+		objects.sort_by(|a, b| {
+			let (a_pos, b_pos) = (a.pos.0, b.pos.0);
+			(a_pos.x, a_pos.y, a_pos.z, &a.element.id).cmp(&(b_pos.x, b_pos.y, b_pos.z, &b.element.id))
+		});
+		Self {objects}
+	}
 }
 
 pub fn store_objects(
-	q: Query<(&object::Pos, &element::Component)>,
+	q: Query<(&object::Pos, &element::Component, &orientation::Orientation)>,
 	mut cmds: Commands,
 ) {
-	let mut sv = CreationData::new();
+	cmds.insert_resource(CreationData::from_objects(&q));
+}
+
+///Recreates the objects of the last stored creation, so they're not lost when switching states
+pub fn restore_objects(
+	creation: Option<Res<CreationData>>,
+	mut create: EventWriter<object::event::Create>,
+	mut history: ResMut<history::History>,
+) {
+	let Some(creation) = creation else {return};
+	// the objects were already there, so recreating them isn't something to undo
+	history.ignore_frame();
 	
-	for (pos, elemc) in &q {
-		sv.objects.push(object::Object {
-			element: elemc.0.clone(),
-			pos: pos.clone(),
-		})
+	for object in &creation.objects {
+		create.send(object::event::Create {
+			pos: object.pos.clone(),
+			element: object.element.clone(),
+			orientation: object.orientation,
+		});
 	}
-	
-	cmds.insert_resource(sv);
 }
 
 
 pub fn hotbar_ui( 
 	mut contexts: bevy_egui::EguiContexts,
 	catalogue: Res<element::Catalogue>,
-	mut selected: ResMut<Hand>,
+	hand: Option<Res<Hand>>,
+	mut hand_orientation: ResMut<HandOrientation>,
+	mut tool: ResMut<Tool>,
+	mut symmetry: ResMut<symmetry::Symmetry>,
+	mut shape_settings: ResMut<shapes::ShapeSettings>,
+	mut cmds: Commands,
 ) {
 	use bevy_egui::egui;
 	let Some(ctx) = contexts.try_ctx_mut() else {
@@ -46,6 +86,31 @@ pub fn hotbar_ui(
 	};
 	
 	egui::Window::new("Hotbar").resizable(true).default_height(50.).show(ctx, |ui| {
+		ui.horizontal(|ui| {
+			for option in Tool::ALL {
+				ui.selectable_value(&mut *tool, option, option.name());
+			}
+		});
+		ui.horizontal(|ui| {
+			ui.label(format!("Rotation: {}", hand_orientation.0));
+			for (label, hint, axis) in [
+				("Yaw", "R, around Y", IVec3::Y),
+				("Pitch", "T, around Z", IVec3::Z),
+				("Roll", "G, around X", IVec3::X),
+			] {
+				if ui.button(label).on_hover_text(hint).clicked() {
+					hand_orientation.0 = hand_orientation.0.rotated(axis);
+				}
+			}
+			if ui.button("Reset").clicked() {
+				hand_orientation.0 = default();
+			}
+		});
+		if tool.is_shape() {
+			shapes::shape_ui(ui, &mut shape_settings);
+		}
+		symmetry::symmetry_ui(ui, &mut symmetry);
+		
 		ui.with_layout(egui::Layout {
 			main_dir: egui::Direction::LeftToRight,
 			main_wrap: false,
@@ -55,15 +120,21 @@ pub fn hotbar_ui(
 			cross_justify: false,
 		}, |ui| {
 			for elem in &catalogue.elements {
-				let button = egui::Button::new(&elem.id)
+				let in_hand = hand.as_ref().is_some_and(|hand| hand.0.id == elem.id);
+				let button = egui::Button::new(&elem.name)
+					.selected(in_hand)
 					.min_size((40.,40.).into());
 				let button_res = ui.add(button);
 				
-				if button_res.clicked() {
-					*selected = Hand(elem.clone());
+				if button_res.on_hover_text(&elem.id).clicked() {
+					cmds.insert_resource(Hand(elem.clone()));
 				}
 			}
-		})
+		});
+		
+		for err in &catalogue.errors {
+			ui.colored_label(egui::Color32::RED, err);
+		}
 	});
 }
 
@@ -9,19 +9,28 @@ Coordinate system:
 - Z+ is to the right, making it a right-handed system
 */
 
-use std::sync::Arc;
-
 use bevy::{
 	input::common_conditions::input_just_pressed,
 	prelude::*
 };
 use derive_more::{From, Into};
 
+use crate::element;
+
 
 pub mod object;
 pub mod input;
 pub mod misc;
-pub mod element;
+pub mod storage;
+pub mod history;
+pub mod grid;
+pub mod orientation;
+pub mod ghost;
+pub mod symmetry;
+pub mod selection;
+pub mod shapes;
+pub mod stats;
+pub mod validation;
 
 
 pub struct EditorPlugin<State: States> {
@@ -32,14 +41,32 @@ impl<State: States> Plugin for EditorPlugin<State> {
 	fn build(&self, app: &mut App) {
 		app
 			.add_event::<object::event::Create>()
-			.init_resource::<element::Catalogue>()
+			.add_event::<object::event::Delete>()
+			.add_event::<object::event::Move>()
+			.add_event::<object::event::Replace>()
+			.init_resource::<storage::SaveState>()
+			.init_resource::<history::History>()
+			.init_resource::<grid::Occupancy>()
+			.init_resource::<Tool>()
+			.init_resource::<HandOrientation>()
+			.init_resource::<ghost::GhostMaterials>()
+			.init_resource::<ghost::Hovered>()
+			.init_resource::<symmetry::Symmetry>()
+			.init_resource::<selection::Clipboard>()
+			.init_resource::<selection::BoxSelect>()
+			.init_resource::<shapes::ShapeSettings>()
+			.init_resource::<stats::CreationStats>()
+			.init_resource::<validation::Validation>()
 		;
 		app.add_systems(OnEnter(self.state.clone()), (
 			create_root,
 			(
 				misc::setup_lights,
 				input::setup_camera,
-			).after(create_root)
+				misc::restore_objects,
+				ghost::setup_ghost,
+			).after(create_root),
+			storage::refresh_saved,
 		));
 		app.add_systems(OnExit(self.state.clone()), (
 			(
@@ -49,60 +76,85 @@ impl<State: States> Plugin for EditorPlugin<State> {
 		));
 		app.add_systems(Update, (
 				create_test_obj
-					.run_if(input_just_pressed(KeyCode::Enter)),
+					.run_if(input_just_pressed(KeyCode::Enter))
+					.run_if(resource_exists::<Hand>)
+					.before(object::ApplyEvents),
 				input::click_handler
+					.run_if(resource_exists::<Hand>)
+					.run_if(not(resource_exists::<selection::Pasting>))
 					.before(input::move_camera)
-					.before(object::create_event_handler),
-				object::create_event_handler,
+					.before(object::ApplyEvents),
+				(
+					selection::select_input
+						.run_if(resource_equals(Tool::Select))
+						.run_if(not(resource_exists::<selection::Pasting>)),
+					selection::selection_input,
+					selection::move_selection,
+					selection::update_paste
+						.run_if(resource_exists::<selection::Pasting>)
+						.after(ghost::track_hover),
+				).before(object::ApplyEvents),
+				selection::draw_selection,
+				(
+					shapes::start_drag
+						.run_if(|tool: Res<Tool>| tool.is_shape())
+						.run_if(not(resource_exists::<selection::Pasting>)),
+					shapes::update_drag
+						.run_if(resource_exists::<shapes::ShapeDrag>),
+				).chain().run_if(resource_exists::<Hand>).before(object::ApplyEvents),
+				object::refresh_elements
+					.run_if(resource_changed::<element::Catalogue>),
+				(
+					object::delete_event_handler,
+					object::move_event_handler,
+					object::create_event_handler,
+					object::replace_event_handler,
+				).chain().in_set(object::ApplyEvents),
+				history::commit.after(object::ApplyEvents),
+				history::undo_redo_input.before(object::ApplyEvents),
+				history::history_ui.before(object::ApplyEvents),
 				input::move_camera,
+				input::rotate_hand,
+				ghost::track_hover,
+				ghost::update_ghost
+					.after(ghost::track_hover)
+					.after(object::ApplyEvents)
+					.after(input::rotate_hand),
+				symmetry::toggle_input,
+				symmetry::draw_planes,
 				misc::hotbar_ui,
+				(
+					stats::update_stats,
+					(
+						stats::draw_center_of_mass,
+						stats::stats_ui,
+					),
+				).chain().after(object::ApplyEvents),
+				(
+					validation::update_validation,
+					validation::draw_disconnected,
+				).chain().after(object::ApplyEvents),
+				storage::storage_ui
+					.before(object::ApplyEvents),
 			)
 			.run_if(in_state(self.state.clone()))
 		);
+		app.add_systems(Update, update_hand.run_if(resource_changed::<element::Catalogue>));
 	}
 }
 
 
-pub fn setup_catalogue(
-	mut catalogue: ResMut<element::Catalogue>,
-	mut meshes: ResMut<Assets<Mesh>>,
-	mut materials: ResMut<Assets<StandardMaterial>>,
+///Keeps [Hand] pointing at an element of the current [element::Catalogue]
+pub fn update_hand(
+	catalogue: Res<element::Catalogue>,
+	hand: Option<Res<Hand>>,
 	mut cmds: Commands,
 ) {
-	let cube = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
-	let material = materials.add(StandardMaterial {
-		base_color: Color::srgb(0.9, 0.85, 0.8),
-		perceptual_roughness: 0.9,
-		..default()
-	});
-	let green = materials.add(StandardMaterial {
-		base_color: Color::srgb(0.2, 0.9, 0.2),
-		perceptual_roughness: 0.9,
-		..default()
-	});
-	
-	let elements = vec![
-		element::Element {
-			graphics: element::Graphics {
-				material,
-				mesh: cube.clone()
-			},
-			id: "block".into(),
-			collider: avian3d::collision::Collider::cuboid(1., 1., 1.),
-		},
-		element::Element {
-			graphics: element::Graphics {
-				material: green,
-				mesh: cube
-			},
-			id: "green_block".into(),
-			collider: avian3d::collision::Collider::cuboid(1., 1., 1.),
-		},
-	];
-	
-	catalogue.elements.extend(elements.into_iter().map(Arc::new));
-	
-	cmds.insert_resource(Hand(catalogue.elements.first().unwrap().clone()));
+	let current = hand.and_then(|hand| catalogue.get(&hand.0.id));
+	match current.or_else(|| catalogue.elements.first().cloned()) {
+		Some(elem) => cmds.insert_resource(Hand(elem)),
+		None => cmds.remove_resource::<Hand>(),
+	}
 }
 
 
@@ -114,6 +166,52 @@ pub struct EditorRoot(pub Entity);
 #[derive(Resource)]
 pub struct Hand(pub element::Ref);
 
+///How the [Hand] element gets rotated when placed.
+/// Separate from [Hand] so it stays the same when picking another element.
+#[derive(Resource, Default)]
+pub struct HandOrientation(pub orientation::Orientation);
+
+///What clicking on an object does
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tool {
+	///Places the [Hand] element against the clicked face
+	#[default]
+	Place,
+	///Removes the clicked object
+	Remove,
+	///Changes the clicked object into the [Hand] element
+	Replace,
+	///Selects objects, see [selection]
+	Select,
+	///Places a line of [Hand] elements, see [shapes]
+	Line,
+	///Places a filled box of [Hand] elements, see [shapes]
+	Fill,
+	///Places the outside of a box of [Hand] elements, see [shapes]
+	HollowBox,
+}
+
+impl Tool {
+	pub const ALL: [Self; 7] = [Self::Place, Self::Remove, Self::Replace, Self::Select, Self::Line, Self::Fill, Self::HollowBox];
+	
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Place => "Place",
+			Self::Remove => "Remove",
+			Self::Replace => "Replace",
+			Self::Select => "Select",
+			Self::Line => "Line",
+			Self::Fill => "Fill",
+			Self::HollowBox => "Hollow Box",
+		}
+	}
+	
+	///Whether this is one of the tools from [shapes]
+	pub fn is_shape(&self) -> bool {
+		matches!(self, Self::Line | Self::Fill | Self::HollowBox)
+	}
+}
+
 
 
 
@@ -130,7 +228,12 @@ fn create_root(
 fn cleanup_root(
 	mut cmds: Commands,
 	root: Res<EditorRoot>,
+	mut grid: ResMut<grid::Occupancy>,
 ) {
+	grid.clear();
+	// the preview is a child of the root
+	cmds.remove_resource::<selection::Pasting>();
+	cmds.remove_resource::<shapes::ShapeDrag>();
 	let root = root.0;
 	cmds.entity(root).despawn_recursive();
 	cmds.remove_resource::<EditorRoot>();
@@ -140,10 +243,12 @@ fn cleanup_root(
 fn create_test_obj(
 	mut oe: EventWriter<object::event::Create>,
 	hand: Res<Hand>,
+	hand_orientation: Res<HandOrientation>,
 ) {
 	oe.send(object::event::Create {
 		pos: IVec3::new(0, 0, 0).into(),
 		element: hand.0.clone(),
+		orientation: hand_orientation.0,
 	});
 }
 
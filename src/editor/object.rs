@@ -7,52 +7,225 @@ THe type of an object is it's element.
 
 
 
-use bevy::prelude::*;
+use bevy::{
+	ecs::system::SystemParam,
+	prelude::*,
+};
 use derive_more::derive::{From, Into};
 
-use super::EditorRoot;
-use super::element;
+use super::{EditorRoot, grid, history, orientation::Orientation};
+use crate::element;
 
 
 ///Position of an object within a creation
 #[derive(Component, Clone, From, Into)]
 pub struct Pos(pub IVec3);
 
+impl Pos {
+	///Where the [Transform] of an object of `element` at this position with `orientation` should be.
+	/// The position is the lowest corner of the object, while the transform is at the center.
+	pub fn transform(&self, element: &element::Element, orientation: Orientation) -> Transform {
+		let pos = self.0.as_vec3();
+		let offset = orientation.rotate_size(element.size).as_vec3() / 2.;
+		Transform::from_translation(pos + offset).with_rotation(orientation.quat())
+	}
+}
+
 
 ///Object info separate from ECS
+#[derive(Clone)]
 pub struct Object {
 	pub element: element::Ref,
 	pub pos: Pos,
+	pub orientation: Orientation,
 }
 
 
+///The systems applying the [event]s, systems sending them should run before this
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApplyEvents;
+
+
 ///Creates objects when [event::Create] happen
 pub fn create_event_handler(
 	mut objs: EventReader<event::Create>,
 	root: Res<EditorRoot>,
+	mut grid: ResMut<grid::Occupancy>,
+	mut history: ResMut<history::History>,
 	mut cmd: Commands,
 ) {
 	for obj_ev in objs.read() {
 		let event::Create {
 			pos,
 			element,
+			orientation,
 		} = obj_ev;
-		let object_pos = pos.0;
-		let object_size = IVec3::new(1,1,1);
 		
-		let pos = object_pos.as_vec3();
-		let offset = object_size.as_vec3() / 2.;
-		let transform = Transform::from_translation(pos + offset);
+		if !grid.is_free(pos.0, orientation.rotate_size(element.size), None) {
+			warn!(pos=?pos.0, element=element.id, "not creating object, space is already occupied");
+			continue;
+		}
 		
-		cmd.spawn((
+		let entity = cmd.spawn((
 			Mesh3d(element.graphics.mesh.clone()),
 			MeshMaterial3d(element.graphics.material.clone()),
-			transform,
+			pos.transform(element, *orientation),
 		))
 		.set_parent(root.0)
 		.insert(Name::new(format!("Object of {}",element.id)))
-		.insert(Pos::from(object_pos))
-		.insert(element::Component::from(element.clone()));
+		.insert(pos.clone())
+		.insert(*orientation)
+		.insert(element::Component::from(element.clone()))
+		.id();
+		
+		grid.insert(entity, grid::Placed {
+			pos: pos.0,
+			element: element.clone(),
+			orientation: *orientation,
+		});
+		history.record(history::Action::Create {
+			pos: pos.0,
+			element: element.clone(),
+			orientation: *orientation,
+		});
+	}
+}
+
+///Removes objects when [event::Delete] happen
+pub fn delete_event_handler(
+	mut events: EventReader<event::Delete>,
+	mut grid: ResMut<grid::Occupancy>,
+	mut history: ResMut<history::History>,
+	mut cmd: Commands,
+) {
+	for event in events.read() {
+		let Some(entity) = grid.get(event.pos.0) else {
+			warn!(pos=?event.pos.0, "trying to delete nonexistent object");
+			continue;
+		};
+		let Some(placed) = grid.remove(entity) else {continue};
+		
+		cmd.entity(entity).despawn_recursive();
+		
+		history.record(history::Action::Delete {
+			pos: placed.pos,
+			element: placed.element,
+			orientation: placed.orientation,
+		});
+	}
+}
+
+///Moves objects when [event::Move] happen
+pub fn move_event_handler(
+	mut events: EventReader<event::Move>,
+	mut grid: ResMut<grid::Occupancy>,
+	mut history: ResMut<history::History>,
+	mut cmd: Commands,
+) {
+	for event in events.read() {
+		let Some((entity, placed)) = grid.object_at(event.from.0) else {
+			warn!(from=?event.from.0, "trying to move nonexistent object");
+			continue;
+		};
+		// `from` can be any cell of the object
+		let to = placed.pos + (event.to.0 - event.from.0);
+		if !grid.is_free(to, placed.size(), Some(entity)) {
+			warn!(from=?placed.pos, ?to, "not moving object, space is already occupied");
+			continue;
+		}
+		let Some(placed) = grid.remove(entity) else {continue};
+		
+		let pos = Pos(to);
+		cmd.entity(entity).insert((
+			pos.transform(&placed.element, placed.orientation),
+			pos,
+		));
+		
+		history.record(history::Action::Move {
+			from: placed.pos,
+			to,
+		});
+		grid.insert(entity, grid::Placed {
+			pos: to,
+			..placed
+		});
+	}
+}
+
+///Changes the element of objects when [event::Replace] happen
+pub fn replace_event_handler(
+	mut events: EventReader<event::Replace>,
+	mut grid: ResMut<grid::Occupancy>,
+	mut history: ResMut<history::History>,
+	mut cmd: Commands,
+) {
+	for event in events.read() {
+		let element = &event.element;
+		let Some((entity, placed)) = grid.object_at(event.pos.0) else {
+			warn!(pos=?event.pos.0, "trying to replace nonexistent object");
+			continue;
+		};
+		if !grid.is_free(placed.pos, placed.orientation.rotate_size(element.size), Some(entity)) {
+			warn!(pos=?placed.pos, element=element.id, "not replacing object, new element doesn't fit");
+			continue;
+		}
+		let Some(old) = grid.remove(entity) else {continue};
+		
+		let pos = Pos(old.pos);
+		cmd.entity(entity).insert((
+			Mesh3d(element.graphics.mesh.clone()),
+			MeshMaterial3d(element.graphics.material.clone()),
+			pos.transform(element, old.orientation),
+			Name::new(format!("Object of {}",element.id)),
+			element::Component::from(element.clone()),
+		));
+		
+		history.record(history::Action::Replace {
+			pos: old.pos,
+			old: old.element,
+			new: element.clone(),
+		});
+		grid.insert(entity, grid::Placed {
+			element: element.clone(),
+			..old
+		});
+	}
+}
+
+
+///Points objects to the newest version of their element after the [element::Catalogue] got rebuilt
+pub fn refresh_elements(
+	catalogue: Res<element::Catalogue>,
+	mut objs: Query<(
+		Entity,
+		&Pos,
+		&Orientation,
+		&mut element::Component,
+		&mut Transform,
+		&mut Mesh3d,
+		&mut MeshMaterial3d<StandardMaterial>,
+	)>,
+	mut grid: ResMut<grid::Occupancy>,
+) {
+	for (entity, pos, orientation, mut elemc, mut transform, mut mesh, mut material) in &mut objs {
+		// objects of removed elements keep their old version
+		let Some(element) = catalogue.get(&elemc.0.id) else {continue};
+		// so do objects that would overlap others after growing
+		if !grid.is_free(pos.0, orientation.rotate_size(element.size), Some(entity)) {
+			warn!(element=element.id, pos=%pos.0, "object doesn't fit anymore with the changed element, keeping the old version");
+			continue;
+		}
+		
+		mesh.0 = element.graphics.mesh.clone();
+		material.0 = element.graphics.material.clone();
+		*transform = pos.transform(&element, *orientation);
+		if let Some(placed) = grid.remove(entity) {
+			grid.insert(entity, grid::Placed {
+				element: element.clone(),
+				..placed
+			});
+		}
+		elemc.0 = element;
 	}
 }
 
@@ -67,5 +240,35 @@ pub mod event {
 	pub struct Create {
 		pub pos: Pos,
 		pub element: element::Ref,
+		pub orientation: Orientation,
+	}
+	
+	///Removes the object at a position
+	#[derive(Event)]
+	pub struct Delete {
+		pub pos: Pos,
+	}
+	
+	///Moves the object at a position somewhere else
+	#[derive(Event)]
+	pub struct Move {
+		pub from: Pos,
+		pub to: Pos,
+	}
+	
+	///Changes the element of the object at a position
+	#[derive(Event)]
+	pub struct Replace {
+		pub pos: Pos,
+		pub element: element::Ref,
+	}
+	
+	///All the writers for object events
+	#[derive(SystemParam)]
+	pub struct Writers<'w> {
+		pub create: EventWriter<'w, Create>,
+		pub delete: EventWriter<'w, Delete>,
+		pub moves: EventWriter<'w, Move>,
+		pub replace: EventWriter<'w, Replace>,
 	}
 }
\ No newline at end of file
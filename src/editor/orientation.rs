@@ -0,0 +1,151 @@
+/*!
+Axis-aligned rotations of objects.
+
+There are 24 of them: 6 directions the element's X+ (forwards) can point in, times 4 rolls around that direction.
+*/
+
+use core::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+
+///One of the 24 axis-aligned rotations of an object
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedOrientation")]
+pub struct Orientation {
+	///Which direction the element's X+ points to: X+, X-, Y+, Y-, Z+, Z-
+	facing: u8,
+	///Amount of quarter turns around the facing direction
+	roll: u8,
+}
+
+///A deserialized [Orientation] that might be out of range
+#[derive(Deserialize)]
+struct UncheckedOrientation {
+	facing: u8,
+	roll: u8,
+}
+
+impl TryFrom<UncheckedOrientation> for Orientation {
+	type Error = String;
+	
+	fn try_from(unchecked: UncheckedOrientation) -> Result<Self, Self::Error> {
+		let UncheckedOrientation {facing, roll} = unchecked;
+		if facing >= 6 {
+			return Err(format!("facing {facing} should be below 6"));
+		}
+		if roll >= 4 {
+			return Err(format!("roll {roll} should be below 4"));
+		}
+		Ok(Self {facing, roll})
+	}
+}
+
+impl Orientation {
+	pub fn all() -> impl Iterator<Item = Self> {
+		(0..6).flat_map(|facing| (0..4).map(move |roll| Self {facing, roll}))
+	}
+	
+	pub fn quat(&self) -> Quat {
+		let face = match self.facing {
+			0 => Quat::IDENTITY,
+			1 => Quat::from_rotation_y(2. * FRAC_PI_2),
+			2 => Quat::from_rotation_z(FRAC_PI_2),
+			3 => Quat::from_rotation_z(-FRAC_PI_2),
+			4 => Quat::from_rotation_y(-FRAC_PI_2),
+			_ => Quat::from_rotation_y(FRAC_PI_2),
+		};
+		face * Quat::from_rotation_x(self.roll as f32 * FRAC_PI_2)
+	}
+	
+	///The orientation closest to the given rotation
+	pub fn from_quat(quat: Quat) -> Self {
+		Self::all()
+			.max_by(|a, b| {
+				let a = a.quat().dot(quat).abs();
+				let b = b.quat().dot(quat).abs();
+				a.total_cmp(&b)
+			})
+			.expect("there should be orientations")
+	}
+	
+	///This orientation, turned a quarter turn around `axis` (in creation space). Negative axes turn the other way.
+	pub fn rotated(&self, axis: IVec3) -> Self {
+		let turn = Quat::from_axis_angle(axis.as_vec3().normalize(), FRAC_PI_2);
+		Self::from_quat(turn * self.quat())
+	}
+	
+	///The orientation of the mirror image of this one, mirrored across a plane with the given `normal`.
+	/// Elements themselves can't be mirrored, so this mirrors where their X+ and Y+ point to, which makes their Z+ point the other way.
+	pub fn mirrored(&self, normal: IVec3) -> Self {
+		let mirror = Mat3::from_diagonal(Vec3::ONE - 2. * normal.abs().as_vec3());
+		// flipping the element's own Z turns the reflection back into a rotation
+		let rotation = mirror * Mat3::from_quat(self.quat()) * Mat3::from_diagonal(Vec3::new(1., 1., -1.));
+		Self::from_quat(Quat::from_mat3(&rotation))
+	}
+	
+	pub fn rotate(&self, vec: IVec3) -> IVec3 {
+		(self.quat() * vec.as_vec3()).round().as_ivec3()
+	}
+	
+	///How many cells an element of `size` covers in every direction when rotated like this
+	pub fn rotate_size(&self, size: IVec3) -> IVec3 {
+		self.rotate(size).abs()
+	}
+}
+
+impl std::fmt::Display for Orientation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let forward = match self.facing {
+			0 => "X+",
+			1 => "X-",
+			2 => "Y+",
+			3 => "Y-",
+			4 => "Z+",
+			_ => "Z-",
+		};
+		write!(f, "forward {forward}, roll {}°", self.roll as u32 * 90)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn mirrored_flips_forward_and_up_across_the_plane() {
+		for normal in [IVec3::X, IVec3::Z] {
+			let mirror = IVec3::ONE - 2 * normal;
+			for orientation in Orientation::all() {
+				let mirrored = orientation.mirrored(normal);
+				assert_eq!(mirrored.rotate(IVec3::X), orientation.rotate(IVec3::X) * mirror, "X+ of {orientation} across {normal}");
+				assert_eq!(mirrored.rotate(IVec3::Y), orientation.rotate(IVec3::Y) * mirror, "Y+ of {orientation} across {normal}");
+				assert_eq!(mirrored.rotate(IVec3::Z), -orientation.rotate(IVec3::Z) * mirror, "Z+ of {orientation} across {normal}");
+			}
+		}
+	}
+	
+	#[test]
+	fn deserialize_checks_range() {
+		for orientation in Orientation::all() {
+			let text = ron::to_string(&orientation).unwrap();
+			assert_eq!(ron::from_str::<Orientation>(&text).unwrap(), orientation);
+		}
+		assert!(ron::from_str::<Orientation>("(facing: 9, roll: 0)").is_err());
+		assert!(ron::from_str::<Orientation>("(facing: 0, roll: 7)").is_err());
+	}
+	
+	#[test]
+	fn mirrored_turns_forwards_around() {
+		let mirrored = Orientation::default().mirrored(IVec3::X);
+		assert_eq!(mirrored.rotate(IVec3::X), IVec3::NEG_X);
+		assert_eq!(mirrored.rotate(IVec3::Y), IVec3::Y);
+		
+		let facing_z = Orientation::all().find(|orientation| orientation.rotate(IVec3::X) == IVec3::Z).unwrap();
+		let mirrored = facing_z.mirrored(IVec3::Z);
+		assert_eq!(mirrored.rotate(IVec3::X), IVec3::NEG_Z);
+		assert_eq!(mirrored.rotate(IVec3::Y), facing_z.rotate(IVec3::Y));
+	}
+}
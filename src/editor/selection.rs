@@ -0,0 +1,359 @@
+/*!
+Selecting groups of objects, and copying, pasting and moving them.
+
+With [Tool::Select], clicking selects an object (ctrl/shift to add or remove it), and dragging selects everything within a box on the screen.
+The selection can then be copied (Ctrl+C), cut (Ctrl+X), deleted (Delete), or moved a cell at a time with the arrow keys and Page Up/Down.
+Pasting (Ctrl+V) shows the copied objects following the cursor until clicked to place them, or until Escape is pressed.
+*/
+
+use bevy::{
+	color::palettes::css,
+	pbr::NotShadowCaster,
+	prelude::*,
+	utils::HashSet,
+	window::PrimaryWindow,
+};
+
+use super::*;
+
+
+///How far the cursor has to move before a click becomes a box selection, in logical pixels
+const DRAG_THRESHOLD: f32 = 5.;
+
+
+///Marks objects that are selected
+#[derive(Component)]
+pub struct Selected;
+
+
+///An object in the [Clipboard]
+#[derive(Clone)]
+pub struct CopiedObject {
+	///Position relative to the lowest corner of the group
+	pub offset: IVec3,
+	pub element: element::Ref,
+	pub orientation: orientation::Orientation,
+}
+
+///The objects that got copied
+#[derive(Resource, Default)]
+pub struct Clipboard {
+	pub objects: Vec<CopiedObject>,
+	///Size of the box around all the objects
+	pub size: IVec3,
+}
+
+impl Clipboard {
+	pub fn from_objects<'a>(objects: impl IntoIterator<Item = &'a grid::Placed>) -> Self {
+		let objects = objects.into_iter().collect::<Vec<_>>();
+		let Some(min) = objects.iter().map(|placed| placed.pos).reduce(IVec3::min) else {
+			return Self::default();
+		};
+		let max = objects.iter().map(|placed| placed.pos + placed.size()).fold(min, IVec3::max);
+		
+		Self {
+			objects: objects.into_iter()
+				.map(|placed| CopiedObject {
+					offset: placed.pos - min,
+					element: placed.element.clone(),
+					orientation: placed.orientation,
+				})
+				.collect(),
+			size: max - min,
+		}
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.objects.is_empty()
+	}
+}
+
+
+///Active while the [Clipboard] is being pasted
+#[derive(Resource)]
+pub struct Pasting {
+	///Parent of the previews of the objects being pasted
+	preview: Entity,
+	///Where the lowest corner of the group would be placed
+	pos: Option<IVec3>,
+}
+
+///Marks the previews of objects being pasted
+#[derive(Component)]
+pub struct PastePreview;
+
+
+///Where the current box selection drag started, in logical viewport pixels
+#[derive(Resource, Default)]
+pub struct BoxSelect {
+	start: Option<Vec2>,
+}
+
+
+fn modifier_pressed(keys: &ButtonInput<KeyCode>) -> bool {
+	keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight, KeyCode::ShiftLeft, KeyCode::ShiftRight])
+}
+
+///Handles selecting by clicking and by dragging a box
+pub fn select_input(
+	mut clicks: EventReader<Pointer<Click>>,
+	mouse: Res<ButtonInput<MouseButton>>,
+	keys: Res<ButtonInput<KeyCode>>,
+	windows: Query<&Window, With<PrimaryWindow>>,
+	cameras: Query<(&Camera, &GlobalTransform), With<input::EditorCamera>>,
+	objects: Query<(Entity, &GlobalTransform, Has<Selected>), With<object::Pos>>,
+	mut box_select: ResMut<BoxSelect>,
+	mut contexts: bevy_egui::EguiContexts,
+	mut cmds: Commands,
+) {
+	let cursor = windows.get_single().ok().and_then(Window::cursor_position);
+	let additive = modifier_pressed(&keys);
+	
+	if mouse.just_pressed(MouseButton::Left) {
+		box_select.start = cursor;
+	}
+	let Some(start) = box_select.start else {
+		clicks.clear();
+		return;
+	};
+	let end = cursor.unwrap_or(start);
+	let rect = Rect::from_corners(start, end);
+	let dragging = start.distance(end) > DRAG_THRESHOLD;
+	
+	if dragging {
+		if let Some(ctx) = contexts.try_ctx_mut() {
+			use bevy_egui::egui;
+			let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("box select")));
+			painter.rect_stroke(
+				egui::Rect::from_two_pos(egui::pos2(start.x, start.y), egui::pos2(end.x, end.y)),
+				0.,
+				egui::Stroke::new(1., egui::Color32::YELLOW),
+			);
+		}
+	}
+	
+	if !mouse.just_released(MouseButton::Left) {return}
+	box_select.start = None;
+	
+	if dragging {
+		// the release also counts as a click if it's on the object the drag started on
+		clicks.clear();
+		let Ok((camera, camera_tf)) = cameras.get_single() else {return};
+		for (entity, tf, selected) in &objects {
+			let inside = camera.world_to_viewport(camera_tf, tf.translation())
+				.is_ok_and(|pos| rect.contains(pos));
+			if inside && !selected {
+				cmds.entity(entity).insert(Selected);
+			} else if !inside && selected && !additive {
+				cmds.entity(entity).remove::<Selected>();
+			}
+		}
+		return;
+	}
+	
+	for click in clicks.read() {
+		if click.button != PointerButton::Primary {continue}
+		let Ok((target, _, target_selected)) = objects.get(click.target) else {continue};
+		
+		if additive {
+			if target_selected {
+				cmds.entity(target).remove::<Selected>();
+			} else {
+				cmds.entity(target).insert(Selected);
+			}
+		} else {
+			for (entity, _, selected) in &objects {
+				if selected && entity != target {
+					cmds.entity(entity).remove::<Selected>();
+				}
+			}
+			cmds.entity(target).insert(Selected);
+		}
+	}
+}
+
+
+///Shortcuts acting on the selection
+pub fn selection_input(
+	keys: Res<ButtonInput<KeyCode>>,
+	selected: Query<Entity, With<Selected>>,
+	grid: Res<grid::Occupancy>,
+	mut clipboard: ResMut<Clipboard>,
+	pasting: Option<Res<Pasting>>,
+	mut writers: object::event::Writers,
+	root: Res<EditorRoot>,
+	materials: Res<ghost::GhostMaterials>,
+	mut cmds: Commands,
+) {
+	let control = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+	let placed = selected.iter()
+		.filter_map(|entity| grid.object(entity))
+		.collect::<Vec<_>>();
+	
+	if keys.just_pressed(KeyCode::Escape) {
+		if let Some(pasting) = pasting {
+			cmds.entity(pasting.preview).despawn_recursive();
+			cmds.remove_resource::<Pasting>();
+		} else {
+			for entity in &selected {
+				cmds.entity(entity).remove::<Selected>();
+			}
+		}
+		return;
+	}
+	
+	if control && (keys.just_pressed(KeyCode::KeyC) || keys.just_pressed(KeyCode::KeyX)) && !placed.is_empty() {
+		*clipboard = Clipboard::from_objects(placed.iter().copied());
+	}
+	if (control && keys.just_pressed(KeyCode::KeyX)) || keys.just_pressed(KeyCode::Delete) {
+		for placed in &placed {
+			writers.delete.send(object::event::Delete {
+				pos: placed.pos.into(),
+			});
+		}
+	}
+	if control && keys.just_pressed(KeyCode::KeyV) && !clipboard.is_empty() && pasting.is_none() {
+		let preview = spawn_preview(&clipboard, &materials, &mut cmds);
+		cmds.entity(preview).set_parent(root.0);
+		cmds.insert_resource(Pasting {
+			preview,
+			pos: None,
+		});
+	}
+}
+
+fn spawn_preview(
+	clipboard: &Clipboard,
+	materials: &ghost::GhostMaterials,
+	cmds: &mut Commands,
+) -> Entity {
+	cmds.spawn((
+		Transform::default(),
+		Visibility::Hidden,
+		Name::new("Paste Preview"),
+	)).with_children(|parent| {
+		for object in &clipboard.objects {
+			parent.spawn((
+				PastePreview,
+				Mesh3d(object.element.graphics.mesh.clone()),
+				MeshMaterial3d(materials.valid.clone()),
+				object::Pos(object.offset).transform(&object.element, object.orientation),
+				NotShadowCaster,
+				PickingBehavior::IGNORE,
+			));
+		}
+	}).id()
+}
+
+
+///Moves the paste preview to the hovered object, and places the objects when clicking
+pub fn update_paste(
+	mut clicks: EventReader<Pointer<Click>>,
+	hovered: Res<ghost::Hovered>,
+	objects: Query<(&object::Pos, &element::Component, &orientation::Orientation)>,
+	clipboard: Res<Clipboard>,
+	mut pasting: ResMut<Pasting>,
+	grid: Res<grid::Occupancy>,
+	materials: Res<ghost::GhostMaterials>,
+	mut previews: Query<(&mut Transform, &mut Visibility), Without<PastePreview>>,
+	mut preview_materials: Query<&mut MeshMaterial3d<StandardMaterial>, With<PastePreview>>,
+	mut create: EventWriter<object::event::Create>,
+	mut cmds: Commands,
+) {
+	pasting.pos = hovered.0.as_ref().and_then(|(entity, hit)| {
+		let (pos, elemc, orientation) = objects.get(*entity).ok()?;
+		let (Some(hit_position), Some(hit_normal)) = (hit.position, hit.normal) else {return None};
+		let size = orientation.rotate_size(elemc.0.size);
+		input::placement_pos(pos.0, size, hit_position, hit_normal, clipboard.size)
+	});
+	let valid = pasting.pos.is_some_and(|pos| {
+		clipboard.objects.iter().all(|object| {
+			grid.is_free(pos + object.offset, object.orientation.rotate_size(object.element.size), None)
+		})
+	});
+	
+	let Ok((mut transform, mut visibility)) = previews.get_mut(pasting.preview) else {return};
+	match pasting.pos {
+		Some(pos) => {
+			transform.translation = pos.as_vec3();
+			*visibility = Visibility::Inherited;
+		},
+		None => *visibility = Visibility::Hidden,
+	}
+	let new_material = if valid {&materials.valid} else {&materials.invalid};
+	for mut material in &mut preview_materials {
+		if material.0 != *new_material {
+			material.0 = new_material.clone();
+		}
+	}
+	
+	let confirmed = clicks.read().any(|click| click.button == PointerButton::Primary);
+	if !confirmed || !valid {return}
+	let Some(pos) = pasting.pos else {return};
+	
+	for object in &clipboard.objects {
+		create.send(object::event::Create {
+			pos: (pos + object.offset).into(),
+			element: object.element.clone(),
+			orientation: object.orientation,
+		});
+	}
+	cmds.entity(pasting.preview).despawn_recursive();
+	cmds.remove_resource::<Pasting>();
+}
+
+
+///Moves the selection a cell at a time: arrow keys for horizontally, Page Up/Down for vertically
+pub fn move_selection(
+	keys: Res<ButtonInput<KeyCode>>,
+	selected: Query<Entity, With<Selected>>,
+	grid: Res<grid::Occupancy>,
+	mut moves: EventWriter<object::event::Move>,
+) {
+	let step = [
+		(KeyCode::ArrowUp, IVec3::X),
+		(KeyCode::ArrowDown, IVec3::NEG_X),
+		(KeyCode::ArrowRight, IVec3::Z),
+		(KeyCode::ArrowLeft, IVec3::NEG_Z),
+		(KeyCode::PageUp, IVec3::Y),
+		(KeyCode::PageDown, IVec3::NEG_Y),
+	].into_iter().find(|(key, _)| keys.just_pressed(*key));
+	let Some((_, step)) = step else {return};
+	
+	let entities = selected.iter().collect::<HashSet<_>>();
+	let mut placed = entities.iter()
+		.filter_map(|entity| grid.object(*entity))
+		.collect::<Vec<_>>();
+	
+	// the whole group should fit, cells of other selected objects will be freed up
+	let fits = placed.iter()
+		.flat_map(|placed| placed.cells())
+		.filter_map(|cell| grid.get(cell + step))
+		.all(|entity| entities.contains(&entity));
+	if !fits {
+		info!(?step, "not moving selection, space is already occupied");
+		return;
+	}
+	
+	// the moves get applied one by one, so the objects in front have to move out of the way first
+	placed.sort_by_key(|placed| -(placed.pos * 2 + placed.size()).dot(step));
+	for placed in placed {
+		moves.send(object::event::Move {
+			from: placed.pos.into(),
+			to: (placed.pos + step).into(),
+		});
+	}
+}
+
+
+pub fn draw_selection(
+	selected: Query<(&object::Pos, &element::Component, &orientation::Orientation), With<Selected>>,
+	mut gizmos: Gizmos,
+) {
+	for (pos, elemc, orientation) in &selected {
+		let size = orientation.rotate_size(elemc.0.size).as_vec3();
+		let center = pos.0.as_vec3() + size / 2.;
+		// slightly bigger so it doesn't z-fight with the object
+		gizmos.cuboid(Transform::from_translation(center).with_scale(size + 0.05), css::YELLOW);
+	}
+}
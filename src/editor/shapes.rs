@@ -0,0 +1,201 @@
+/*!
+Tools that place the [Hand] element across a dragged range of cells: [Tool::Line], [Tool::Fill] and [Tool::HollowBox].
+
+Pressing on a face starts where the first object would be placed with [Tool::Place].
+Dragging then stretches the shape along the plane of that face, and releasing places all the objects at once, as a single undo step.
+The shape can be made multiple layers thick away from the face.
+*/
+
+use bevy::{
+	color::palettes::css,
+	prelude::*,
+	utils::HashSet,
+	window::PrimaryWindow,
+};
+
+use super::*;
+
+
+///Shapes bigger than this are probably a mistake
+const MAX_OBJECTS: usize = 4096;
+
+
+#[derive(Resource)]
+pub struct ShapeSettings {
+	///How many objects thick shapes are, away from the face they're started on
+	pub layers: u32,
+}
+
+impl Default for ShapeSettings {
+	fn default() -> Self {
+		Self {
+			layers: 1,
+		}
+	}
+}
+
+
+///A shape being dragged out
+#[derive(Resource)]
+pub struct ShapeDrag {
+	///Position of the first object
+	start: IVec3,
+	///Normal of the face the drag started on
+	normal: IVec3,
+	///Where the cursor currently is, in steps of the size of the [Hand] element, relative to `start`
+	end_step: IVec3,
+}
+
+impl ShapeDrag {
+	///The range of steps the shape covers, including the layers
+	fn step_range(&self, layers: u32) -> (IVec3, IVec3) {
+		let depth = self.normal * (layers.max(1) as i32 - 1);
+		let end = self.end_step + depth;
+		(end.min(IVec3::ZERO), end.max(IVec3::ZERO))
+	}
+	
+	///Positions of the objects that make up the shape, in steps relative to `start`
+	fn steps(&self, tool: Tool, layers: u32) -> Vec<IVec3> {
+		let (min, max) = self.step_range(layers);
+		match tool {
+			Tool::Line => {
+				let depth = self.normal * (layers.max(1) as i32 - 1);
+				let layer_range = (depth.min(IVec3::ZERO), depth.max(IVec3::ZERO));
+				let line = line_steps(self.end_step);
+				grid::cells(layer_range.0, layer_range.1 - layer_range.0 + IVec3::ONE)
+					.flat_map(|layer| line.iter().map(move |step| *step + layer))
+					.collect()
+			},
+			Tool::Fill => grid::cells(min, max - min + IVec3::ONE).collect(),
+			Tool::HollowBox => grid::cells(min, max - min + IVec3::ONE)
+				.filter(|step| on_shell(*step, min, max))
+				.collect(),
+			_ => Vec::new(),
+		}
+	}
+}
+
+///The steps on a straight line from zero to `end`
+fn line_steps(end: IVec3) -> Vec<IVec3> {
+	let count = end.abs().max_element();
+	if count == 0 {
+		return vec![IVec3::ZERO];
+	}
+	(0..=count)
+		.map(|i| (end.as_vec3() * i as f32 / count as f32).round().as_ivec3())
+		.collect()
+}
+
+///Whether `step` is on the outside of the box from `min` to `max`.
+/// Axes the box is only one step thick along are ignored, so flat boxes become outlines.
+fn on_shell(step: IVec3, min: IVec3, max: IVec3) -> bool {
+	let mut thick_axes = (0..3).filter(|axis| min[*axis] != max[*axis]).peekable();
+	if thick_axes.peek().is_none() {return true}
+	thick_axes.any(|axis| step[axis] == min[axis] || step[axis] == max[axis])
+}
+
+
+///Starts a drag when pressing on an object with one of the shape tools
+pub fn start_drag(
+	mut presses: EventReader<Pointer<Down>>,
+	objects: Query<(&object::Pos, &element::Component, &orientation::Orientation)>,
+	hand: Res<Hand>,
+	hand_orientation: Res<HandOrientation>,
+	mut cmds: Commands,
+) {
+	for press in presses.read() {
+		if press.button != PointerButton::Primary {continue}
+		let Ok(clicked) = objects.get(press.target) else {continue};
+		let Some(normal) = press.hit.normal.and_then(input::face_normal) else {continue};
+		let Some(start) = input::hand_placement(&press.hit, clicked, &hand, &hand_orientation) else {continue};
+		
+		cmds.insert_resource(ShapeDrag {
+			start,
+			normal,
+			end_step: IVec3::ZERO,
+		});
+	}
+}
+
+///Stretches the shape to the cursor, and places it when the mouse is released
+pub fn update_drag(
+	mut drag: ResMut<ShapeDrag>,
+	mouse: Res<ButtonInput<MouseButton>>,
+	keys: Res<ButtonInput<KeyCode>>,
+	windows: Query<&Window, With<PrimaryWindow>>,
+	cameras: Query<(&Camera, &GlobalTransform), With<input::EditorCamera>>,
+	hand: Res<Hand>,
+	hand_orientation: Res<HandOrientation>,
+	tool: Res<Tool>,
+	settings: Res<ShapeSettings>,
+	symmetry: Res<symmetry::Symmetry>,
+	grid: Res<grid::Occupancy>,
+	mut create: EventWriter<object::event::Create>,
+	mut gizmos: Gizmos,
+	mut cmds: Commands,
+) {
+	if keys.just_pressed(KeyCode::Escape) || !tool.is_shape() {
+		cmds.remove_resource::<ShapeDrag>();
+		return;
+	}
+	let size = hand_orientation.0.rotate_size(hand.0.size);
+	
+	// follow the cursor across the plane of the face the drag started on
+	let cursor = windows.get_single().ok().and_then(Window::cursor_position);
+	let camera = cameras.get_single().ok();
+	if let (Some(cursor), Some((camera, camera_tf))) = (cursor, camera) {
+		let plane_origin = drag.start.as_vec3() + size.as_vec3() / 2.;
+		let plane = InfinitePlane3d::new(drag.normal.as_vec3());
+		let hit = camera.viewport_to_world(camera_tf, cursor).ok()
+			.and_then(|ray| Some(ray.get_point(ray.intersect_plane(plane_origin, plane)?)));
+		if let Some(point) = hit {
+			let cell = point.floor().as_ivec3();
+			let along_plane = IVec3::ONE - drag.normal.abs();
+			drag.end_step = (cell - drag.start).div_euclid(size) * along_plane;
+		}
+	}
+	
+	let (min, max) = drag.step_range(settings.layers);
+	let box_min = drag.start + min * size;
+	let box_size = (max - min + IVec3::ONE) * size;
+	gizmos.cuboid(
+		Transform::from_translation(box_min.as_vec3() + box_size.as_vec3() / 2.).with_scale(box_size.as_vec3() + 0.05),
+		css::LIME,
+	);
+	
+	if !mouse.just_released(MouseButton::Left) {return}
+	cmds.remove_resource::<ShapeDrag>();
+	
+	let steps = drag.steps(*tool, settings.layers);
+	if steps.len() > MAX_OBJECTS {
+		warn!(count = steps.len(), "not placing shape, it has too many objects");
+		return;
+	}
+	
+	let mut placed = HashSet::new();
+	for step in steps {
+		let pos = drag.start + step * size;
+		for (pos, orientation) in symmetry.images(pos, size, hand_orientation.0) {
+			// existing objects just get built around
+			if !placed.insert(pos) || !grid.is_free(pos, size, None) {continue}
+			create.send(object::event::Create {
+				pos: pos.into(),
+				element: hand.0.clone(),
+				orientation,
+			});
+		}
+	}
+}
+
+
+pub fn shape_ui(
+	ui: &mut bevy_egui::egui::Ui,
+	settings: &mut ShapeSettings,
+) {
+	use bevy_egui::egui;
+	
+	ui.horizontal(|ui| {
+		ui.label("Layers");
+		ui.add(egui::DragValue::new(&mut settings.layers).range(1..=64));
+	});
+}
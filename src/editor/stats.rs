@@ -0,0 +1,107 @@
+/*!
+Shows what the creation will be like as a vessel, using the same data the [crate::vessel_builder] uses.
+*/
+
+use bevy::{
+	color::palettes::css,
+	prelude::*,
+};
+
+use super::*;
+use crate::vessel_builder;
+
+
+///Stats of the creation currently in the editor
+#[derive(Resource, Default)]
+pub struct CreationStats(pub vessel_builder::Stats);
+
+
+///Recalculates the [CreationStats] when objects changed
+pub fn update_stats(
+	changed: Query<(), Or<(Changed<object::Pos>, Changed<element::Component>, Changed<orientation::Orientation>)>>,
+	mut removed: RemovedComponents<object::Pos>,
+	objects: Query<(&object::Pos, &element::Component, &orientation::Orientation)>,
+	mut stats: ResMut<CreationStats>,
+) {
+	let any_removed = removed.read().count() > 0;
+	if changed.is_empty() && !any_removed {return}
+	
+	let creation = misc::CreationData::from_objects(&objects);
+	stats.0 = vessel_builder::creation_stats(&creation);
+}
+
+
+pub fn draw_center_of_mass(
+	stats: Res<CreationStats>,
+	mut gizmos: Gizmos,
+) {
+	if stats.0.object_count == 0 {return}
+	let com = stats.0.mass.center_of_mass;
+	
+	gizmos.sphere(Isometry3d::from_translation(com), 0.25, css::FUCHSIA);
+	for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+		gizmos.line(com - axis * 0.5, com + axis * 0.5, css::FUCHSIA);
+	}
+}
+
+
+pub fn stats_ui(
+	mut contexts: bevy_egui::EguiContexts,
+	stats: Res<CreationStats>,
+) {
+	use bevy_egui::egui;
+	let Some(ctx) = contexts.try_ctx_mut() else {
+		// Primary window is missing, because it still is being initialized or has been closed
+		// This system can still run in those conditions, so just do nothing until other systems fix it
+		return;
+	};
+	let stats = &stats.0;
+	
+	egui::Window::new("Stats").resizable(true).default_open(false).show(ctx, |ui| {
+		egui::Grid::new("stats").num_columns(2).show(ui, |ui| {
+			ui.label("Objects");
+			ui.label(stats.object_count.to_string());
+			ui.end_row();
+			
+			ui.label("Mass");
+			ui.label(format!("{:.1}", stats.mass.mass));
+			ui.end_row();
+			
+			let com = stats.mass.center_of_mass;
+			ui.label("Centre of mass");
+			ui.label(format!("{:.2}, {:.2}, {:.2}", com.x, com.y, com.z));
+			ui.end_row();
+			
+			let inertia = &stats.mass.inertia;
+			ui.label("Inertia").on_hover_text("Around the X (roll), Y (yaw) and Z (pitch) axes through the centre of mass");
+			ui.label(format!("{:.1}, {:.1}, {:.1}", inertia.x_axis.x, inertia.y_axis.y, inertia.z_axis.z));
+			ui.end_row();
+			
+			if let Some((min, max)) = stats.bounds {
+				let size = max - min;
+				ui.label("Size");
+				ui.label(format!("{} x {} x {}", size.x, size.y, size.z))
+					.on_hover_text(format!("From {min} to {max}"));
+				ui.end_row();
+			}
+			
+			let properties = &stats.properties;
+			for (name, value) in [
+				("Base forwards force", properties.control_forwards_force),
+				("Thruster force", stats.thruster_force),
+				("Turning torque", properties.control_torque),
+				("Side friction", properties.side_friction),
+				("Drag", properties.drag),
+			] {
+				ui.label(name);
+				ui.label(format!("{value:.1}"));
+				ui.end_row();
+			}
+		});
+		
+		ui.separator();
+		for (name, count) in &stats.counts {
+			ui.label(format!("{count} x {name}"));
+		}
+	});
+}
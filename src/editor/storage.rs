@@ -0,0 +1,444 @@
+/*!
+Saving and loading creations to and from files.
+
+Creations are stored as RON files in [SAVE_DIR].
+Objects are stored by element id, so the files stay valid when the graphics or physics of an element change.
+*/
+
+use std::{
+	fs,
+	path::{Component, Path, PathBuf},
+};
+
+use bevy::{
+	prelude::*,
+	utils::HashSet,
+};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+
+///Directory creations get saved in, relative to the working directory
+pub const SAVE_DIR: &str = "creations";
+///File extension used for saved creations
+pub const EXTENSION: &str = "creation.ron";
+///Version of [CreationFile] this build writes.
+/// Should be incremented when the format changes in an incompatible manner.
+pub const FORMAT_VERSION: u32 = 1;
+
+
+///Serializable form of a creation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreationFile {
+	pub version: u32,
+	pub objects: Vec<ObjectData>,
+}
+
+///Serializable form of an [object::Object]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObjectData {
+	///Id of the element
+	pub element: String,
+	pub pos: IVec3,
+	///Files from before rotation existed don't have this
+	#[serde(default)]
+	pub orientation: orientation::Orientation,
+}
+
+impl From<&misc::CreationData> for CreationFile {
+	fn from(creation: &misc::CreationData) -> Self {
+		Self {
+			version: FORMAT_VERSION,
+			objects: creation.objects.iter()
+				.map(|object| ObjectData {
+					element: object.element.id.clone(),
+					pos: object.pos.0,
+					orientation: object.orientation,
+				})
+				.collect(),
+		}
+	}
+}
+
+///Objects that got skipped when turning a [CreationFile] into a creation
+#[derive(Default, Debug)]
+pub struct Skipped {
+	///Ids of the elements that aren't in the catalogue, without duplicates
+	pub unknown: Vec<String>,
+	///Amount of objects that overlapped with objects earlier in the file
+	pub overlapping: usize,
+}
+
+impl Skipped {
+	pub fn is_empty(&self) -> bool {
+		self.unknown.is_empty() && self.overlapping == 0
+	}
+}
+
+impl std::fmt::Display for Skipped {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let mut parts = Vec::new();
+		if !self.unknown.is_empty() {
+			parts.push(format!("unknown elements: {}", self.unknown.join(", ")));
+		}
+		if self.overlapping > 0 {
+			parts.push(format!("{} overlapping objects", self.overlapping));
+		}
+		write!(f, "{}", parts.join(", "))
+	}
+}
+
+impl CreationFile {
+	///Looks up the elements of all the objects in the `catalogue`.
+	///Objects with unknown elements or that overlap earlier objects are skipped.
+	pub fn to_creation(&self, catalogue: &element::Catalogue) -> (misc::CreationData, Skipped) {
+		let mut creation = misc::CreationData::new();
+		let mut skipped = Skipped::default();
+		let mut occupied = HashSet::new();
+		
+		for object in &self.objects {
+			let Some(element) = catalogue.get(&object.element) else {
+				if !skipped.unknown.contains(&object.element) {
+					skipped.unknown.push(object.element.clone());
+				}
+				continue;
+			};
+			
+			let cells = grid::cells(object.pos, object.orientation.rotate_size(element.size)).collect::<Vec<_>>();
+			if cells.iter().any(|cell| occupied.contains(cell)) {
+				skipped.overlapping += 1;
+				continue;
+			}
+			occupied.extend(cells);
+			
+			creation.objects.push(object::Object {
+				element,
+				pos: object.pos.into(),
+				orientation: object.orientation,
+			});
+		}
+		
+		(creation, skipped)
+	}
+}
+
+
+#[derive(Debug)]
+pub enum Error {
+	Io(std::io::Error),
+	Parse(ron::error::SpannedError),
+	Serialize(ron::Error),
+	UnsupportedVersion(u32),
+	InvalidName(String),
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io(err) => write!(f, "file error: {err}"),
+			Self::Parse(err) => write!(f, "invalid creation file: {err}"),
+			Self::Serialize(err) => write!(f, "could not serialize creation: {err}"),
+			Self::UnsupportedVersion(version) => write!(f, "unsupported creation format version {version}, expected {FORMAT_VERSION}"),
+			Self::InvalidName(name) => write!(f, "invalid name {name:?}, it can't contain path separators or \"..\""),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+
+///Whether `name` can be used as a file name inside [SAVE_DIR] without ending up somewhere else
+pub fn is_valid_name(name: &str) -> bool {
+	let mut components = Path::new(name).components();
+	!name.contains(['/', '\\'])
+		&& !name.contains("..")
+		&& matches!(components.next(), Some(Component::Normal(component)) if component == name)
+		&& components.next().is_none()
+}
+
+///Path of the file a creation with the given name gets stored in
+pub fn path_for(name: &str) -> Result<PathBuf, Error> {
+	if !is_valid_name(name) {
+		return Err(Error::InvalidName(name.to_owned()));
+	}
+	Ok(PathBuf::from(SAVE_DIR).join(format!("{name}.{EXTENSION}")))
+}
+
+pub fn save(name: &str, file: &CreationFile) -> Result<(), Error> {
+	let path = path_for(name)?;
+	let text = ron::ser::to_string_pretty(file, ron::ser::PrettyConfig::default())
+		.map_err(Error::Serialize)?;
+	fs::create_dir_all(SAVE_DIR).map_err(Error::Io)?;
+	fs::write(path, text).map_err(Error::Io)
+}
+
+pub fn load(name: &str) -> Result<CreationFile, Error> {
+	let text = fs::read_to_string(path_for(name)?).map_err(Error::Io)?;
+	parse(&text)
+}
+
+///Reads the contents of a creation file, which should be of the current [FORMAT_VERSION]
+pub fn parse(text: &str) -> Result<CreationFile, Error> {
+	let file: CreationFile = ron::from_str(text).map_err(Error::Parse)?;
+	if file.version != FORMAT_VERSION {
+		return Err(Error::UnsupportedVersion(file.version));
+	}
+	Ok(file)
+}
+
+///Names of all the saved creations, sorted alphabetically
+pub fn list_saved() -> Result<Vec<String>, Error> {
+	let entries = match fs::read_dir(SAVE_DIR) {
+		Ok(entries) => entries,
+		// nothing has been saved yet
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+		Err(err) => return Err(Error::Io(err)),
+	};
+	
+	let suffix = format!(".{EXTENSION}");
+	let mut names = Vec::new();
+	for entry in entries {
+		let entry = entry.map_err(Error::Io)?;
+		let file_name = entry.file_name();
+		let Some(name) = file_name.to_str().and_then(|name| name.strip_suffix(&suffix)) else {continue};
+		names.push(name.to_owned());
+	}
+	names.sort();
+	
+	Ok(names)
+}
+
+
+///State of the save/load ui
+#[derive(Resource, Default)]
+pub struct SaveState {
+	///Name of the file the creation was last saved to or loaded from
+	pub current: Option<String>,
+	///Contents of the name text field
+	pub name_input: String,
+	pub saved: Vec<String>,
+	///Result of the last action, shown to the user
+	pub status: Option<Result<String, String>>,
+}
+
+impl SaveState {
+	fn refresh(&mut self) {
+		match list_saved() {
+			Ok(saved) => self.saved = saved,
+			Err(err) => self.status = Some(Err(err.to_string())),
+		}
+	}
+}
+
+
+pub fn refresh_saved(
+	mut state: ResMut<SaveState>,
+) {
+	state.refresh();
+}
+
+
+enum Action {
+	Save(String),
+	Load(String),
+}
+
+pub fn storage_ui(
+	mut contexts: bevy_egui::EguiContexts,
+	mut state: ResMut<SaveState>,
+	objects: Query<(Entity, &object::Pos, &element::Component, &orientation::Orientation)>,
+	catalogue: Res<element::Catalogue>,
+	mut create: EventWriter<object::event::Create>,
+	mut history: ResMut<history::History>,
+	mut grid: ResMut<grid::Occupancy>,
+	mut cmds: Commands,
+) {
+	use bevy_egui::egui;
+	let Some(ctx) = contexts.try_ctx_mut() else {
+		// Primary window is missing, because it still is being initialized or has been closed
+		// This system can still run in those conditions, so just do nothing until other systems fix it
+		return;
+	};
+	
+	let mut action = None;
+	
+	egui::Window::new("Creation").resizable(true).show(ctx, |ui| {
+		match &state.current {
+			Some(current) => ui.label(format!("Current file: {current}")),
+			None => ui.label("Not saved yet"),
+		};
+		
+		ui.horizontal(|ui| {
+			let current = state.current.clone();
+			if ui.add_enabled(current.is_some(), egui::Button::new("Save")).clicked() {
+				action = current.map(Action::Save);
+			}
+			
+			ui.text_edit_singleline(&mut state.name_input);
+			let name = state.name_input.trim().to_owned();
+			if ui.add_enabled(!name.is_empty(), egui::Button::new("Save As")).clicked() {
+				if is_valid_name(&name) {
+					action = Some(Action::Save(name));
+				} else {
+					state.status = Some(Err(Error::InvalidName(name).to_string()));
+				}
+			}
+		});
+		
+		match &state.status {
+			Some(Ok(msg)) => {ui.label(msg);},
+			Some(Err(msg)) => {ui.colored_label(egui::Color32::RED, msg);},
+			None => {},
+		}
+		
+		ui.separator();
+		ui.horizontal(|ui| {
+			ui.label("Saved creations");
+			if ui.button("Refresh").clicked() {
+				state.refresh();
+			}
+		});
+		egui::ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+			for name in &state.saved {
+				ui.horizontal(|ui| {
+					if ui.button("Load").clicked() {
+						action = Some(Action::Load(name.clone()));
+					}
+					ui.label(name);
+				});
+			}
+		});
+	});
+	
+	match action {
+		Some(Action::Save(name)) => {
+			let creation = misc::CreationData::from_objects(objects.iter().map(|(_, pos, elemc, orientation)| (pos, elemc, orientation)));
+			match save(&name, &CreationFile::from(&creation)) {
+				Ok(()) => {
+					info!(name, "saved creation");
+					state.status = Some(Ok(format!("Saved {name}")));
+					state.current = Some(name);
+				},
+				Err(err) => {
+					error!(name, %err, "failed to save creation");
+					state.status = Some(Err(format!("Failed to save {name}: {err}")));
+				},
+			}
+			state.refresh();
+		},
+		Some(Action::Load(name)) => {
+			match load(&name) {
+				Ok(file) => {
+					let (creation, skipped) = file.to_creation(&catalogue);
+					
+					// the old objects are removed directly, so the history wouldn't make sense anymore
+					history.clear();
+					history.ignore_frame();
+					
+					for (entity, ..) in &objects {
+						cmds.entity(entity).despawn_recursive();
+					}
+					grid.clear();
+					for object in creation.objects {
+						create.send(object::event::Create {
+							pos: object.pos,
+							element: object.element,
+							orientation: object.orientation,
+						});
+					}
+					
+					if skipped.is_empty() {
+						info!(name, "loaded creation");
+						state.status = Some(Ok(format!("Loaded {name}")));
+					} else {
+						warn!(name, ?skipped, "loaded creation with skipped objects");
+						state.status = Some(Err(format!("Loaded {name}, skipped {skipped}")));
+					}
+					state.current = Some(name);
+				},
+				Err(err) => {
+					error!(name, %err, "failed to load creation");
+					state.status = Some(Err(format!("Failed to load {name}: {err}")));
+				},
+			}
+		},
+		None => {},
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn names_stay_inside_the_save_dir() {
+		for name in ["boat", "my boat", "boat.v2", "böot"] {
+			assert!(is_valid_name(name), "{name:?} should be valid");
+		}
+		for name in ["", ".", "..", "../../foo", "foo/bar", "foo\\bar", "/foo", "foo/", "a..b"] {
+			assert!(!is_valid_name(name), "{name:?} should be invalid");
+		}
+	}
+	
+	fn object(element: &str, pos: IVec3) -> ObjectData {
+		ObjectData {
+			element: element.into(),
+			pos,
+			orientation: default(),
+		}
+	}
+	
+	#[test]
+	fn file_round_trip() {
+		let file = CreationFile {
+			version: FORMAT_VERSION,
+			objects: vec![
+				object("block", IVec3::new(1, -2, 3)),
+				ObjectData {
+					orientation: orientation::Orientation::default().rotated(IVec3::Y),
+					..object("beam", IVec3::ZERO)
+				},
+			],
+		};
+		let text = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).unwrap();
+		let parsed = parse(&text).unwrap();
+		
+		assert_eq!(parsed.objects.len(), file.objects.len());
+		for (parsed, original) in parsed.objects.iter().zip(&file.objects) {
+			assert_eq!(parsed.element, original.element);
+			assert_eq!(parsed.pos, original.pos);
+			assert_eq!(parsed.orientation, original.orientation);
+		}
+	}
+	
+	#[test]
+	fn parse_rejects_other_versions() {
+		let text = format!("(version: {}, objects: [])", FORMAT_VERSION + 1);
+		assert!(matches!(parse(&text), Err(Error::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1));
+		assert!(matches!(parse("(objects: [])"), Err(Error::Parse(_))));
+	}
+	
+	#[test]
+	fn to_creation_skips_unknown_and_overlapping() {
+		let catalogue = element::Catalogue::shipped();
+		let file = CreationFile {
+			version: FORMAT_VERSION,
+			objects: vec![
+				object("block", IVec3::ZERO),
+				object("not_an_element", IVec3::X * 5),
+				// 4 long, so it overlaps the block
+				object("beam", IVec3::new(-2, 0, 0)),
+				object("not_an_element", IVec3::X * 7),
+				object("beam", IVec3::Y),
+			],
+		};
+		let (creation, skipped) = file.to_creation(&catalogue);
+		
+		assert_eq!(skipped.unknown, vec!["not_an_element".to_owned()]);
+		assert_eq!(skipped.overlapping, 1);
+		let placed = creation.objects.iter().map(|object| (object.element.id.as_str(), object.pos.0)).collect::<Vec<_>>();
+		assert_eq!(placed, vec![("block", IVec3::ZERO), ("beam", IVec3::Y)]);
+	}
+}
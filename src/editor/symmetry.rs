@@ -0,0 +1,120 @@
+/*!
+Mirror building: every object placed or removed also gets placed or removed at its mirror image.
+
+Mirroring across Z (left/right) is the common case, mirroring across X (front/back) can be turned on as well.
+*/
+
+use bevy::{
+	color::palettes::css,
+	prelude::*,
+};
+
+use super::*;
+
+
+///A mirror plane perpendicular to one of the axes
+#[derive(Clone, Copy, Debug)]
+pub struct Mirror {
+	pub enabled: bool,
+	///Position of the plane along its axis, times 2.
+	/// Odd values put the plane through the middle of a cell, even values between cells.
+	pub doubled_pos: i32,
+}
+
+impl Default for Mirror {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			// through the middle of the first object
+			doubled_pos: 1,
+		}
+	}
+}
+
+impl Mirror {
+	///Where something that starts at `start` and is `size` long ends up after mirroring
+	pub fn mirror(&self, start: i32, size: i32) -> i32 {
+		self.doubled_pos - start - size
+	}
+	
+	pub fn world_pos(&self) -> f32 {
+		self.doubled_pos as f32 / 2.
+	}
+}
+
+
+#[derive(Resource, Default, Debug)]
+pub struct Symmetry {
+	///Left/right mirroring
+	pub z: Mirror,
+	///Front/back mirroring
+	pub x: Mirror,
+}
+
+impl Symmetry {
+	///The enabled mirrors with the index of their axis
+	fn mirrors(&self) -> impl Iterator<Item = (&Mirror, usize)> {
+		[(&self.z, 2), (&self.x, 0)]
+			.into_iter()
+			.filter(|(mirror, _)| mirror.enabled)
+	}
+	
+	///Where an object at `pos` with the (rotated) `size` and `orientation` should also go, including itself.
+	/// Doesn't contain duplicate positions, so objects on a mirror plane only show up once.
+	pub fn images(&self, pos: IVec3, size: IVec3, orientation: orientation::Orientation) -> Vec<(IVec3, orientation::Orientation)> {
+		let mut images = vec![(pos, orientation)];
+		for (mirror, axis) in self.mirrors() {
+			for i in 0..images.len() {
+				let (mut image_pos, image_orientation) = images[i];
+				image_pos[axis] = mirror.mirror(image_pos[axis], size[axis]);
+				if images.iter().any(|(other, _)| *other == image_pos) {continue}
+				images.push((image_pos, image_orientation.mirrored(IVec3::AXES[axis])));
+			}
+		}
+		images
+	}
+}
+
+
+pub fn toggle_input(
+	keys: Res<ButtonInput<KeyCode>>,
+	mut symmetry: ResMut<Symmetry>,
+) {
+	if keys.just_pressed(KeyCode::KeyM) {
+		symmetry.z.enabled = !symmetry.z.enabled;
+	}
+}
+
+pub fn draw_planes(
+	symmetry: Res<Symmetry>,
+	mut gizmos: Gizmos,
+) {
+	const CELLS: UVec2 = UVec2::splat(24);
+	
+	if symmetry.z.enabled {
+		let isometry = Isometry3d::from_translation(Vec3::Z * symmetry.z.world_pos());
+		gizmos.grid(isometry, CELLS, Vec2::ONE, css::DEEP_SKY_BLUE.with_alpha(0.5));
+	}
+	if symmetry.x.enabled {
+		// the grid is drawn in the XY plane, so turn it to face X
+		let isometry = Isometry3d::new(Vec3::X * symmetry.x.world_pos(), Quat::from_rotation_y(core::f32::consts::FRAC_PI_2));
+		gizmos.grid(isometry, CELLS, Vec2::ONE, css::ORANGE_RED.with_alpha(0.5));
+	}
+}
+
+pub fn symmetry_ui(
+	ui: &mut bevy_egui::egui::Ui,
+	symmetry: &mut Symmetry,
+) {
+	use bevy_egui::egui;
+	
+	for (name, mirror) in [("Mirror Z", &mut symmetry.z), ("Mirror X", &mut symmetry.x)] {
+		ui.horizontal(|ui| {
+			ui.checkbox(&mut mirror.enabled, name);
+			ui.add_enabled(mirror.enabled, egui::DragValue::new(&mut mirror.doubled_pos)
+				.custom_formatter(|doubled, _| format!("{}", doubled / 2.))
+				.custom_parser(|text| text.parse::<f64>().ok().map(|pos| (pos * 2.).round()))
+			).on_hover_text("Position of the plane");
+		});
+	}
+}
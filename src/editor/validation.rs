@@ -0,0 +1,73 @@
+/*!
+Keeps track of problems that would make the creation a bad vessel, see [vessel_builder::validate].
+*/
+
+use bevy::{
+	color::palettes::css,
+	prelude::*,
+};
+
+use super::*;
+use crate::vessel_builder;
+
+
+///Result of validating the creation currently in the editor
+#[derive(Resource)]
+pub struct Validation {
+	pub problem: Option<vessel_builder::Problem>,
+	///Position and size of objects that aren't connected to the biggest part of the creation
+	pub disconnected: Vec<(IVec3, IVec3)>,
+}
+
+impl Default for Validation {
+	fn default() -> Self {
+		Self {
+			// the editor starts out empty
+			problem: Some(vessel_builder::Problem::Empty),
+			disconnected: Vec::new(),
+		}
+	}
+}
+
+
+///Validates the creation again when objects changed
+pub fn update_validation(
+	changed: Query<(), Or<(Changed<object::Pos>, Changed<element::Component>, Changed<orientation::Orientation>)>>,
+	mut removed: RemovedComponents<object::Pos>,
+	objects: Query<(&object::Pos, &element::Component, &orientation::Orientation)>,
+	mut validation: ResMut<Validation>,
+) {
+	let any_removed = removed.read().count() > 0;
+	if changed.is_empty() && !any_removed {return}
+	
+	let creation = misc::CreationData::from_objects(&objects);
+	let problem = vessel_builder::validate(&creation).err();
+	let disconnected = match &problem {
+		Some(vessel_builder::Problem::Disconnected {islands}) => islands.iter()
+			.skip(1)
+			.flatten()
+			.map(|index| {
+				let object = &creation.objects[*index];
+				(object.pos.0, object.orientation.rotate_size(object.element.size))
+			})
+			.collect(),
+		_ => Vec::new(),
+	};
+	
+	*validation = Validation {
+		problem,
+		disconnected,
+	};
+}
+
+
+pub fn draw_disconnected(
+	validation: Res<Validation>,
+	mut gizmos: Gizmos,
+) {
+	for (pos, size) in &validation.disconnected {
+		let size = size.as_vec3();
+		// slightly bigger so it doesn't z-fight with the object
+		gizmos.cuboid(Transform::from_translation(pos.as_vec3() + size / 2.).with_scale(size + 0.1), css::RED);
+	}
+}
@@ -0,0 +1,506 @@
+/*!
+Everything to do with elements.
+
+Elements are the type of objects.
+They are defined by [Definition] assets in [DEFINITION_DIR], from which the [Catalogue] gets built.
+
+The catalogue is also used by the headless server to validate and simulate vessels,
+so everything related to rendering is only present with the `user_interface` feature.
+*/
+
+
+use bevy::{
+	asset::{
+		io::file::FileAssetReader,
+		AssetLoadFailedEvent,
+	},
+	prelude::*,
+	time::common_conditions::on_timer,
+	utils::HashMap,
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use derive_more::derive::{From, Into};
+use serde::{Deserialize, Serialize};
+use std::{
+	path::PathBuf,
+	sync::Arc,
+	time::Duration,
+};
+
+
+///Folder in the assets folder containing the element definitions
+pub const DEFINITION_DIR: &str = "elements";
+///File extension of element definitions
+pub const DEFINITION_EXTENSION: &str = "element.ron";
+
+
+///Loads the element definitions and keeps the [Catalogue] up to date with them
+pub struct CataloguePlugin;
+
+impl Plugin for CataloguePlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_plugins(RonAssetPlugin::<Definition>::new(&[DEFINITION_EXTENSION]))
+			.init_resource::<Catalogue>()
+			.init_resource::<Definitions>()
+			.add_systems(Startup, scan_definitions)
+			.add_systems(Update, (
+				// picks up newly added definition files, changes to existing ones get hot-reloaded by the asset server
+				scan_definitions.run_if(on_timer(Duration::from_secs(1))),
+				update_catalogue.after(scan_definitions),
+			));
+		
+		#[cfg(feature="user_interface")]
+		app.init_resource::<Placeholder>();
+	}
+}
+
+
+#[cfg(feature="user_interface")]
+pub struct Graphics {
+	pub material: Handle<StandardMaterial>,
+	pub mesh: Handle<Mesh>,
+	///If the mesh is a cuboid exactly filling the element's cells,
+	/// so faces touching another such mesh can be left out when merging meshes
+	pub fills_cells: bool,
+}
+
+
+///What to show in place of elements that aren't in the [Catalogue], e.g. in vessels from a client with other elements.
+/// Their size isn't known, so it's a single cell.
+#[cfg(feature="user_interface")]
+#[derive(Resource)]
+pub struct Placeholder(pub Graphics);
+
+#[cfg(feature="user_interface")]
+impl FromWorld for Placeholder {
+	fn from_world(world: &mut World) -> Self {
+		let mesh = world.resource_mut::<Assets<Mesh>>().add(Cuboid::from_size(Vec3::ONE));
+		let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+			base_color: Color::srgb(1., 0., 1.),
+			unlit: true,
+			..default()
+		});
+		Self(Graphics {
+			material,
+			mesh,
+			fills_cells: false,
+		})
+	}
+}
+
+
+///List of all the available elements
+#[derive(Resource, Default)]
+pub struct Catalogue {
+	pub elements: Vec<Arc<Element>>,
+	///Problems with the element definitions, to show to the user
+	pub errors: Vec<String>,
+}
+
+impl Catalogue {
+	///[None] if the asked for element isn't in this catalogue,
+	/// which can happen for vessels or creations made with other element definitions
+	pub fn get(&self, id: &str) -> Option<Arc<Element>> {
+		self.elements.iter()
+			.find(|elem| elem.id == id)
+			.cloned()
+	}
+	
+	///The elements in the assets folder, without graphics
+	#[cfg(all(test, feature="user_interface"))]
+	pub fn shipped() -> Self {
+		let dir = std::path::Path::new("assets").join(DEFINITION_DIR);
+		let mut elements = Vec::new();
+		for entry in std::fs::read_dir(dir).expect("element definitions directory should exist") {
+			let path = entry.expect("element definitions directory should be readable").path();
+			if !path.to_string_lossy().ends_with(DEFINITION_EXTENSION) {continue}
+			let text = std::fs::read_to_string(&path).expect("element definition should be readable");
+			let definition: Definition = ron::from_str(&text).unwrap_or_else(|err| panic!("{} should parse: {err}", path.display()));
+			definition.validate().unwrap_or_else(|err| panic!("{} should be valid: {err}", path.display()));
+			elements.push(Arc::new(definition.build(Graphics {
+				material: default(),
+				mesh: default(),
+				fills_cells: false,
+			})));
+		}
+		Self {
+			elements,
+			errors: Vec::new(),
+		}
+	}
+}
+
+///Object type
+pub struct Element {
+	pub id: String,
+	///Name to show to the user
+	pub name: String,
+	///Size in grid cells
+	pub size: IVec3,
+	pub mass: f32,
+	pub properties: Properties,
+	pub kind: Kind,
+	///What [Element::collider] got built from
+	pub collider_shape: Shape,
+	pub collider: avian3d::collision::Collider,
+	#[cfg(feature="user_interface")]
+	pub graphics: Graphics,
+}
+
+pub type Ref = Arc<Element>;
+
+#[derive(Component, Into, From)]
+pub struct Component(pub Ref);
+
+
+///Data-driven description of an [Element], loaded from RON files
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Definition {
+	pub id: String,
+	pub name: String,
+	///Size in grid cells
+	#[serde(default = "Definition::default_size")]
+	pub size: IVec3,
+	///Defaults to 1 per grid cell
+	#[serde(default)]
+	pub mass: Option<f32>,
+	pub mesh: MeshSource,
+	#[serde(default)]
+	pub material: MaterialDefinition,
+	pub collider: Shape,
+	#[serde(default)]
+	pub properties: Properties,
+	#[serde(default)]
+	pub kind: Kind,
+}
+
+impl Definition {
+	fn default_size() -> IVec3 {
+		IVec3::ONE
+	}
+	
+	///Checks for problems that can't be caught by deserialization
+	pub fn validate(&self) -> Result<(), String> {
+		if self.id.is_empty() {
+			return Err("id is empty".into());
+		}
+		if self.size.min_element() < 1 {
+			return Err(format!("size {} should be at least 1 in every direction", self.size));
+		}
+		if let Some(mass) = self.mass {
+			if !(mass.is_finite() && mass > 0.) {
+				return Err(format!("mass {mass} should be positive"));
+			}
+		}
+		self.properties.validate()?;
+		self.kind.validate()
+	}
+	
+	pub fn build(
+		&self,
+		#[cfg(feature="user_interface")]
+		graphics: Graphics,
+	) -> Element {
+		Element {
+			id: self.id.clone(),
+			name: self.name.clone(),
+			size: self.size,
+			mass: self.mass.unwrap_or(self.size.element_product() as f32),
+			properties: self.properties.clone(),
+			kind: self.kind.clone(),
+			collider_shape: self.collider,
+			collider: self.collider.collider(self.size.as_vec3()),
+			#[cfg(feature="user_interface")]
+			graphics,
+		}
+	}
+	
+	#[cfg(feature="user_interface")]
+	pub fn build_graphics(
+		&self,
+		meshes: &mut Assets<Mesh>,
+		materials: &mut Assets<StandardMaterial>,
+		assets: &AssetServer,
+	) -> Graphics {
+		let mesh = match &self.mesh {
+			MeshSource::Primitive(shape) => meshes.add(shape.mesh(self.size.as_vec3())),
+			MeshSource::Asset(path) => assets.load(path),
+		};
+		Graphics {
+			material: materials.add(self.material.build()),
+			mesh,
+			fills_cells: matches!(self.mesh, MeshSource::Primitive(Shape::Cuboid)),
+		}
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum MeshSource {
+	///A simple shape filling the element's size
+	Primitive(Shape),
+	///Path to a mesh asset, e.g. `"local/wing.glb#Mesh0/Primitive0"`
+	Asset(String),
+}
+
+///Simple shapes, filling an element's size
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+	Cuboid,
+	///Uses the smallest dimension as diameter
+	Sphere,
+	///Upright (along Y), uses the smallest horizontal dimension as diameter
+	Cylinder,
+}
+
+impl Shape {
+	#[cfg(feature="user_interface")]
+	pub fn mesh(&self, size: Vec3) -> Mesh {
+		match self {
+			Self::Cuboid => Cuboid::from_size(size).into(),
+			Self::Sphere => Sphere::new(size.min_element() / 2.).into(),
+			Self::Cylinder => Cylinder::new(size.x.min(size.z) / 2., size.y).into(),
+		}
+	}
+	
+	pub fn collider(&self, size: Vec3) -> avian3d::collision::Collider {
+		use avian3d::collision::Collider;
+		match self {
+			Self::Cuboid => Collider::cuboid(size.x, size.y, size.z),
+			Self::Sphere => Collider::sphere(size.min_element() / 2.),
+			Self::Cylinder => Collider::cylinder(size.x.min(size.z) / 2., size.y),
+		}
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MaterialDefinition {
+	///sRGB color
+	pub base_color: (f32, f32, f32),
+	pub roughness: f32,
+	pub metallic: f32,
+}
+
+impl Default for MaterialDefinition {
+	fn default() -> Self {
+		Self {
+			base_color: (0.8, 0.8, 0.8),
+			roughness: 0.9,
+			metallic: 0.,
+		}
+	}
+}
+
+#[cfg(feature="user_interface")]
+impl MaterialDefinition {
+	pub fn build(&self) -> StandardMaterial {
+		let (r, g, b) = self.base_color;
+		StandardMaterial {
+			base_color: Color::srgb(r, g, b),
+			perceptual_roughness: self.roughness,
+			metallic: self.metallic,
+			..default()
+		}
+	}
+}
+
+///What an element contributes to how a vessel drives.
+/// These get added up over all the objects of a vessel, see [crate::worldplay::vessel::VesselProperties].
+/// Thrust isn't one of them, it comes from [Kind::Thruster] parts so it gets applied where the thrusters are.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Properties {
+	///Resistance against sliding sideways
+	pub grip: f32,
+	///Resistance against moving through the air
+	pub drag: f32,
+	///Torque for turning left and right
+	pub torque: f32,
+}
+
+impl Properties {
+	pub fn validate(&self) -> Result<(), String> {
+		check_non_negative(&[
+			("grip", self.grip),
+			("drag", self.drag),
+			("torque", self.torque),
+		])
+	}
+}
+
+
+///What an element does on its own location when part of a vessel, see [crate::worldplay::parts]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum Kind {
+	///Just there
+	#[default]
+	Passive,
+	///Pushes along the element's X+ when accelerating, and the other way when braking
+	Thruster {
+		force: f32,
+	},
+	///Holds the vessel up from the ground below it like a spring, and keeps that spot from sliding sideways.
+	/// Also works as a hover pad.
+	Wheel {
+		///How far below the element's centre the ground can be felt
+		suspension_length: f32,
+		///Force per unit of compression
+		stiffness: f32,
+		///Force per unit of compression speed
+		damping: f32,
+		///Force per unit of sideways speed
+		grip: f32,
+	},
+	///Pushes against air moving through it along the element's Y
+	Fin {
+		///Force per unit of speed through the fin
+		lift: f32,
+	},
+}
+
+impl Kind {
+	pub fn validate(&self) -> Result<(), String> {
+		let values: &[(&str, f32)] = match self {
+			Self::Passive => &[],
+			Self::Thruster {force} => &[("force", *force)],
+			Self::Wheel {suspension_length, stiffness, damping, grip} => &[
+				("suspension_length", *suspension_length),
+				("stiffness", *stiffness),
+				("damping", *damping),
+				("grip", *grip),
+			],
+			Self::Fin {lift} => &[("lift", *lift)],
+		};
+		check_non_negative(values)
+	}
+}
+
+///Errors on the first of the named `values` that's negative or not a number
+fn check_non_negative(values: &[(&str, f32)]) -> Result<(), String> {
+	for (name, value) in values {
+		if !(value.is_finite() && *value >= 0.) {
+			return Err(format!("{name} {value} shouldn't be negative"));
+		}
+	}
+	Ok(())
+}
+
+
+///Handles to all the known [Definition] files, and the problems loading them
+#[derive(Resource, Default)]
+pub struct Definitions {
+	pub handles: HashMap<PathBuf, Handle<Definition>>,
+	///Load errors by asset path
+	pub load_errors: HashMap<String, String>,
+}
+
+
+///Asset paths of the files in the `dir` folder of the assets folder that have the given (multi-part) `extension`
+pub fn find_asset_files(dir: &str, extension: &str) -> std::io::Result<Vec<PathBuf>> {
+	let entries = std::fs::read_dir(FileAssetReader::get_base_path().join("assets").join(dir))?;
+	
+	let suffix = format!(".{extension}");
+	let mut found = Vec::new();
+	for entry in entries.flatten() {
+		let file_name = entry.file_name();
+		let Some(file_name) = file_name.to_str() else {continue};
+		if !file_name.ends_with(&suffix) {continue}
+		found.push(PathBuf::from(dir).join(file_name));
+	}
+	Ok(found)
+}
+
+
+///Loads all definition files in [DEFINITION_DIR], and forgets about the ones that have been deleted
+pub fn scan_definitions(
+	mut definitions: ResMut<Definitions>,
+	assets: Res<AssetServer>,
+) {
+	let found = match find_asset_files(DEFINITION_DIR, DEFINITION_EXTENSION) {
+		Ok(found) => found,
+		Err(err) => {
+			warn_once!(dir=DEFINITION_DIR, %err, "could not read element definitions directory");
+			return;
+		},
+	};
+	
+	definitions.handles.retain(|path, _| found.contains(path));
+	definitions.load_errors.retain(|path, _| found.iter().any(|found| found == std::path::Path::new(path)));
+	for path in found {
+		if !definitions.handles.contains_key(&path) {
+			let handle = assets.load(path.clone());
+			definitions.handles.insert(path, handle);
+		}
+	}
+}
+
+
+///Rebuilds the [Catalogue] when the [Definition]s change
+pub fn update_catalogue(
+	mut asset_events: EventReader<AssetEvent<Definition>>,
+	mut failed_events: EventReader<AssetLoadFailedEvent<Definition>>,
+	mut definitions: ResMut<Definitions>,
+	definition_assets: Res<Assets<Definition>>,
+	mut catalogue: ResMut<Catalogue>,
+	#[cfg(feature="user_interface")]
+	mut meshes: ResMut<Assets<Mesh>>,
+	#[cfg(feature="user_interface")]
+	mut materials: ResMut<Assets<StandardMaterial>>,
+	assets: Res<AssetServer>,
+) {
+	let mut changed = false;
+	
+	for event in failed_events.read() {
+		error!(path=%event.path, error=%event.error, "failed to load element definition");
+		definitions.load_errors.insert(event.path.to_string(), event.error.to_string());
+		changed = true;
+	}
+	for event in asset_events.read() {
+		if let AssetEvent::Added {id} | AssetEvent::Modified {id} = event {
+			if let Some(path) = assets.get_path(*id) {
+				definitions.load_errors.remove(&path.to_string());
+			}
+		}
+		changed = true;
+	}
+	
+	if !changed {return}
+	
+	let mut errors = definitions.load_errors.iter()
+		.map(|(path, err)| format!("{path}: {err}"))
+		.collect::<Vec<_>>();
+	let mut elements: Vec<Arc<Element>> = Vec::new();
+	
+	let mut paths = definitions.handles.keys().collect::<Vec<_>>();
+	// so which one of duplicate definitions wins doesn't depend on hashing
+	paths.sort();
+	for path in paths {
+		let handle = &definitions.handles[path];
+		let Some(definition) = definition_assets.get(handle) else {continue};
+		if let Err(err) = definition.validate() {
+			errors.push(format!("{}: {err}", path.display()));
+			continue;
+		}
+		if elements.iter().any(|elem| elem.id == definition.id) {
+			errors.push(format!("{}: duplicate element id {:?}", path.display(), definition.id));
+			continue;
+		}
+		#[cfg(feature="user_interface")]
+		let element = definition.build(definition.build_graphics(&mut meshes, &mut materials, &assets));
+		#[cfg(not(feature="user_interface"))]
+		let element = definition.build();
+		elements.push(Arc::new(element));
+	}
+	
+	for err in &errors {
+		warn!("element definition problem: {err}");
+	}
+	
+	elements.sort_by(|a, b| a.id.cmp(&b.id));
+	errors.sort();
+	*catalogue = Catalogue {
+		elements,
+		errors,
+	};
+}
@@ -1,8 +1,12 @@
-use avian3d::{prelude::Gravity, PhysicsPlugins};
+use avian3d::PhysicsPlugins;
 use bevy::{input::mouse::{MouseButtonInput, MouseWheel}, prelude::*};
 
+#[cfg(feature="user_interface")]
 mod editor;
+mod element;
+mod track;
 mod worldplay;
+#[cfg(feature="user_interface")]
 mod vessel_builder;
 mod multiplayer;
 
@@ -54,8 +58,8 @@ fn main() {
 	
 	app
 	
+	// gravity is set by the track
 	.add_plugins(PhysicsPlugins::default())
-	.insert_resource(Gravity(-Vec3::Y * 15.))
 	
 	.add_plugins((
 		bevy_replicon::RepliconPlugins,
@@ -81,13 +85,12 @@ fn main() {
 		.add_systems(Startup, network::setup_server_system);
 	#[cfg(feature="user_interface")]
 	app.insert_state(GameState::EditVessel)
-		//TODO redesign catalogue so a headless server can use it
-		.add_systems(Startup, editor::setup_catalogue)
 		.add_systems(Startup, setup_ui_style)
 		.add_systems(Update, state_ui)
 		.add_systems(Update, network::network_ui);
 
 	// needed for the server
+	app.add_plugins(element::CataloguePlugin);
 	
 	#[cfg(feature="user_interface")]
 	app.add_plugins(editor::EditorPlugin {
@@ -95,21 +98,22 @@ fn main() {
 	});
 	
 	app
-	.add_plugins(worldplay::GameplayPlugin);
+	.add_plugins(worldplay::GameplayPlugin)
+	.add_plugins(track::TrackPlugin);
 	
 	//Depends on the GameplayPlugin, so should be added later
 	app.add_plugins(multiplayer::MultiplayerPlugin);
 	
+	#[cfg(feature="user_interface")]
 	app.add_systems(OnTransition {
 		exited: GameState::EditVessel,
 		entered: GameState::WorldPlay
-	}, vessel_builder::build_vessel_system)
+	}, vessel_builder::build_vessel_system);
 	
-	.add_systems(OnEnter(GameState::WorldPlay), |mut nw: ResMut<NextState<worldplay::WorldState>>|
+	app.add_systems(OnEnter(GameState::WorldPlay), |mut nw: ResMut<NextState<worldplay::WorldState>>|
 		nw.set(worldplay::WorldState::Foreground))
 	.add_systems(OnEnter(GameState::EditVessel), |mut nw: ResMut<NextState<worldplay::WorldState>>|
 		nw.set(worldplay::WorldState::Background))
-	.add_systems(Startup, setup_demo_track)
 	
 	.run();
 }
@@ -122,32 +126,11 @@ pub enum GameState {
 }
 
 
-fn setup_demo_track(
-	mut cmds: Commands,
-	assets: ResMut<AssetServer>
-) {
-	use avian3d::prelude::*;
-	
-	let scene = assets.load("local/track.glb#Scene0");
-	cmds.spawn((
-		SceneRoot(scene),
-		Transform::from_xyz(0.,-10.,-5.),
-	))
-	.insert(Name::new("World/Track"))
-	.insert(ColliderConstructorHierarchy::new(ColliderConstructor::TrimeshFromMeshWithConfig(
-		TrimeshFlags::MERGE_DUPLICATE_VERTICES
-		| TrimeshFlags::DELETE_DUPLICATE_TRIANGLES 
-		| TrimeshFlags::DELETE_DEGENERATE_TRIANGLES
-		| TrimeshFlags::FIX_INTERNAL_EDGES
-		| TrimeshFlags::DELETE_BAD_TOPOLOGY_TRIANGLES
-	)))
-	.insert(CollisionMargin(0.002)) // should help with stability around trimeshes
-	.insert(RigidBody::Static);
-}
-
-
+#[cfg(feature="user_interface")]
 fn state_ui(
 	mut contexts: bevy_egui::EguiContexts,
+	state: Res<State<GameState>>,
+	validation: Res<editor::validation::Validation>,
 	mut next_state: ResMut<NextState<GameState>>,
 ) {
 	use bevy_egui::egui;
@@ -161,9 +144,19 @@ fn state_ui(
 		if ui.button("Edit").clicked() {
 			next_state.set(GameState::EditVessel);
 		}
-		if ui.button("Play").clicked() {
+		// the vessel gets built from the editor, so it should be buildable
+		let problem = validation.problem.as_ref().filter(|_| *state.get() == GameState::EditVessel);
+		let playable = !problem.is_some_and(|problem| problem.blocks_play());
+		if ui.add_enabled(playable, egui::Button::new("Play")).clicked() {
 			next_state.set(GameState::WorldPlay);
 		}
+		if let Some(problem) = problem {
+			if playable {
+				ui.colored_label(egui::Color32::YELLOW, format!("Warning: {problem}"));
+			} else {
+				ui.colored_label(egui::Color32::RED, format!("Can't play: {problem}"));
+			}
+		}
 	});
 }
 
@@ -230,5 +223,5 @@ fn absorb_egui_inputs(
 			keyboard.press(key);
 		}
 	}
-	
+
 }
\ No newline at end of file
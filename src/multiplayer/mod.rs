@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::{
+	collections::{HashMap, HashSet},
+	path::PathBuf,
+};
 
 use bevy::{
 	color::palettes::css, prelude::*, window::PrimaryWindow
@@ -7,8 +10,12 @@ use bevy_replicon::prelude::*;
 use avian3d::prelude::{AngularVelocity, LinearVelocity, Position, Rotation};
 use serde::{Serialize, Deserialize};
 
-use crate::worldplay::{
-	self, user, vessel, WorldState
+use crate::{
+	element::Catalogue,
+	track::{CurrentTrack, SelectedTrack},
+	worldplay::{
+		self, respawn, user, vessel, WorldState
+	},
 };
 
 
@@ -23,11 +30,16 @@ impl Plugin for MultiplayerPlugin {
 			.add_observer(network::setup_client)
 			.add_observer(network::setup_server)
 			.init_resource::<ClientOwnedEntities>()
+			.init_resource::<SentVessels>()
 			
 			.replicate_group::<(MultiPlayer, vessel::Id, Position, Rotation, LinearVelocity, AngularVelocity)>()
+			.replicate::<respawn::SpawnSlot>()
 			.add_client_event::<vessel::Control>(ChannelKind::Ordered)
+			.add_client_event::<RequestRespawn>(ChannelKind::Ordered)
 			.add_client_event::<NewUserVessel>(ChannelKind::Unordered)
 			.add_server_event::<AddVessel>(ChannelKind::Unordered)
+			.add_server_event::<VesselRejected>(ChannelKind::Unordered)
+			.add_server_event::<TrackSelected>(ChannelKind::Ordered)
 			
 			.add_systems(OnEnter(WorldState::Foreground), send_user_vessel.after(user::spawn_user).run_if(client_connected))
 		;
@@ -36,6 +48,7 @@ impl Plugin for MultiplayerPlugin {
 		app
 			.add_systems(Update, mark_players.after(vessel::move_vessel))
 			.add_systems(Update, ui::debug_ui)
+			.add_systems(Update, ui::rejection_ui)
 			.add_systems(Update, (
 				set_server_window_title.run_if(server_just_started),
 				set_client_window_title.run_if(client_just_connected),
@@ -51,6 +64,10 @@ impl Plugin for MultiplayerPlugin {
 				.before(vessel::move_vessel)
 				.run_if(server_running)
 			)
+			.add_systems(Update, apply_client_respawns
+				.before(respawn::respawn_vessels)
+				.run_if(server_running)
+			)
 			.add_systems(PreUpdate,
 				(
 					spawn_player,
@@ -61,7 +78,11 @@ impl Plugin for MultiplayerPlugin {
 			)
 			.add_systems(PreUpdate, setup_player.after(ClientSet::Receive).run_if(client_connected))
 			.add_systems(PreUpdate, receive_server_vessels.after(ClientSet::Receive).run_if(client_connected))
+			.add_systems(PreUpdate, receive_rejections.after(ClientSet::Receive).run_if(client_connected))
+			.add_systems(PreUpdate, receive_selected_track.after(ClientSet::Receive).run_if(client_connected))
+			.add_systems(PostUpdate, send_selected_track.before(ServerSet::Send).run_if(server_running))
 			.add_systems(PostUpdate, send_movement.before(ClientSet::Send).run_if(client_connected))
+			.add_systems(PostUpdate, send_respawns.before(ClientSet::Send).run_if(client_connected))
 		;
 	}
 }
@@ -101,6 +122,35 @@ pub struct ClientOwnedEntities {
 }
 
 
+///Which vessels each client already has, so they don't need to be sent again
+#[derive(Resource, Default)]
+pub struct SentVessels {
+	pub map: HashMap<ClientId, HashSet<vessel::Id>>,
+}
+
+impl SentVessels {
+	///Sends the vessel to the client, unless it already has it
+	pub fn send(
+		&mut self,
+		client_id: ClientId,
+		vessel_id: vessel::Id,
+		sim_vessel: &vessel::SimVessel,
+		events: &mut EventWriter<ToClients<AddVessel>>,
+	) {
+		if !self.map.entry(client_id).or_default().insert(vessel_id) {
+			return;
+		}
+		events.send(ToClients {
+			mode: SendMode::Direct(client_id),
+			event: AddVessel {
+				vessel_id,
+				sim_vessel: sim_vessel.clone(),
+			},
+		});
+	}
+}
+
+
 #[derive(Component, Serialize, Deserialize)]
 pub struct MultiPlayer;
 
@@ -120,16 +170,68 @@ pub fn apply_client_movement(
 	client_entities: Res<ClientOwnedEntities>,
 ) {
 	for event in events.read() {
-		let target = client_entities.map.get(&event.client_id).expect("client sending us events should have a related entity");
+		let Some(target) = client_entities.map.get(&event.client_id) else {
+			// the client's vessel got rejected
+			continue;
+		};
 		let mut control = query.get_mut(*target).expect("target should exist and have a Control component");
 		control.0 = event.event.0;
 	}
 }
 
 
+///A client wants its vessel to be respawned, see [respawn::Respawn]
+#[derive(Event, Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RequestRespawn {
+	///Only used when it's at a checkpoint, otherwise the server picks the spawn point itself
+	pub transform: Transform,
+}
+
+///The server simulates the vessels, so it has to do the respawning
+pub fn send_respawns(
+	mut respawns: EventReader<respawn::Respawn>,
+	local: Query<(), With<user::LocallyControlled>>,
+	mut events: EventWriter<RequestRespawn>,
+) {
+	for respawn in respawns.read() {
+		if local.contains(respawn.entity) {
+			events.send(RequestRespawn {
+				transform: respawn.transform,
+			});
+		}
+	}
+}
+
+pub fn apply_client_respawns(
+	mut events: EventReader<FromClient<RequestRespawn>>,
+	client_entities: Res<ClientOwnedEntities>,
+	slots: Query<&respawn::SpawnSlot>,
+	track: Res<CurrentTrack>,
+	mut respawns: EventWriter<respawn::Respawn>,
+) {
+	for event in events.read() {
+		let Some(entity) = client_entities.map.get(&event.client_id) else {
+			// the client's vessel got rejected
+			continue;
+		};
+		let requested = event.event.transform;
+		// anything else would let clients teleport wherever they want
+		let transform = if track.0.is_checkpoint(requested.translation) {
+			requested
+		} else {
+			respawn::respawn_transform(slots.get(*entity).ok(), None, &track)
+		};
+		respawns.send(respawn::Respawn {
+			entity: *entity,
+			transform,
+		});
+	}
+}
+
+
 #[derive(Event, serde::Serialize, serde::Deserialize)]
 pub struct AddVessel {
-	///The uuid of the sim_vessel asset
+	///The id of the sim_vessel asset, see [vessel::Id::of]
 	vessel_id: vessel::Id,
 	///The data of the vessel
 	sim_vessel: vessel::SimVessel,
@@ -146,9 +248,70 @@ pub fn receive_server_vessels(
 }
 
 
+///Tells a client the server didn't accept its vessel
+#[derive(Event, Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct VesselRejected {
+	pub vessel_id: vessel::Id,
+	///Ids of the elements the server doesn't know
+	pub unknown: Vec<String>,
+}
+
+///The last [VesselRejected] the server sent us, until the user dismisses it
+#[cfg(feature="user_interface")]
+#[derive(Resource, Debug)]
+pub struct RejectedVessel(pub VesselRejected);
+
+
+pub fn receive_rejections(
+	mut events: EventReader<VesselRejected>,
+	#[cfg(feature="user_interface")]
+	mut cmds: Commands,
+) {
+	for event in events.read() {
+		error!(vessel_id=?event.vessel_id, unknown=?event.unknown, "server rejected our vessel");
+		#[cfg(feature="user_interface")]
+		cmds.insert_resource(RejectedVessel(event.clone()));
+	}
+}
+
+
+///The track the server uses, clients should drive on the same one
+#[derive(Event, Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct TrackSelected {
+	///See [SelectedTrack], clients need to have the same track assets
+	pub path: Option<PathBuf>,
+}
+
+pub fn send_selected_track(
+	selected: Res<SelectedTrack>,
+	mut server_events: EventReader<ServerEvent>,
+	mut events: EventWriter<ToClients<TrackSelected>>,
+) {
+	// clients that just connected don't know it yet
+	let connected = server_events.read().any(|event| matches!(event, ServerEvent::ClientConnected {..}));
+	if !selected.is_changed() && !connected {return}
+	events.send(ToClients {
+		mode: SendMode::Broadcast,
+		event: TrackSelected {
+			path: selected.0.clone(),
+		},
+	});
+}
+
+pub fn receive_selected_track(
+	mut events: EventReader<TrackSelected>,
+	mut selected: ResMut<SelectedTrack>,
+) {
+	for event in events.read() {
+		info!(path=?event.path, "server selected track");
+		selected.set_if_neq(SelectedTrack(event.path.clone()));
+	}
+}
+
+
 #[derive(Event, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct NewUserVessel {
-	///The uuid of the sim_vessel asset
+	///The id of the sim_vessel asset, see [vessel::Id::of]
 	vessel_id: vessel::Id,
 	///The data of the vessel
 	sim_vessel: vessel::SimVessel,
@@ -188,24 +351,55 @@ pub fn spawn_player(
 	mut cmds: Commands,
 	mut new_user_vessel_events: EventReader<FromClient<NewUserVessel>>,
 	mut new_vessel_send: EventWriter<ToClients<AddVessel>>,
+	mut rejection_send: EventWriter<ToClients<VesselRejected>>,
 	mut client_owned_entities: ResMut<ClientOwnedEntities>,
 	mut vessels: ResMut<Assets<vessel::SimVessel>>,
 	mut client_entity_map: ResMut<ClientEntityMap>,
+	mut sent_vessels: ResMut<SentVessels>,
+	connected_clients: Res<ConnectedClients>,
+	catalogue: Res<Catalogue>,
+	slots: Query<&respawn::SpawnSlot>,
 ) {
+	// the slots of the vessels spawned by this system aren't in the query yet
+	let mut taken = slots.iter().copied().collect::<Vec<_>>();
 	for client_event in new_user_vessel_events.read() {
-		vessels.insert(client_event.event.vessel_id.0, client_event.event.sim_vessel.clone());
+		let sim_vessel = &client_event.event.sim_vessel;
+		let claimed_id = client_event.event.vessel_id;
+		// ids are shared between clients, so a wrong one would make them use the wrong data
+		let vessel_id = vessel::Id::of(sim_vessel);
+		let unknown = sim_vessel.unknown_elements(&catalogue);
+		if !unknown.is_empty() {
+			// other clients might not know them either, and we can't simulate them properly
+			warn!(client_id=?client_event.client_id, ?unknown, "rejecting client vessel, it uses elements we don't know");
+			rejection_send.send(ToClients {
+				mode: SendMode::Direct(client_event.client_id),
+				event: VesselRejected {
+					vessel_id: claimed_id,
+					unknown: unknown.into_iter().map(String::from).collect(),
+				},
+			});
+			continue;
+		}
 		
-		new_vessel_send.send(ToClients {
-			mode: SendMode::BroadcastExcept(client_event.client_id),
-			event: AddVessel {
-				vessel_id: client_event.event.vessel_id,
-				sim_vessel: client_event.event.sim_vessel.clone(),
-			}
-		});
+		vessels.insert(vessel_id.0, sim_vessel.clone());
+		if vessel_id == claimed_id {
+			// the sender obviously already has it
+			sent_vessels.map.entry(client_event.client_id).or_default().insert(vessel_id);
+		} else {
+			// it gets sent back under the right id below
+			warn!(client_id=?client_event.client_id, ?claimed_id, ?vessel_id, "client vessel id doesn't match its data, using the right one");
+		}
+		for client in connected_clients.iter() {
+			sent_vessels.send(client.id(), vessel_id, sim_vessel, &mut new_vessel_send);
+		}
+		
+		let slot = respawn::SpawnSlot::free(&taken);
+		taken.push(slot);
 		
 		let id = cmds.spawn(MultiPlayer)
 			.insert(Replicated)
-			.insert(client_event.event.vessel_id)
+			.insert(vessel_id)
+			.insert(slot)
 			.id();
 		
 		client_entity_map.insert(client_event.client_id, ClientMapping {
@@ -225,6 +419,7 @@ pub fn connection_handler(
 	mut new_vessel_send: EventWriter<ToClients<AddVessel>>,
 	mut cmds: Commands,
 	mut client_owned_entities: ResMut<ClientOwnedEntities>,
+	mut sent_vessels: ResMut<SentVessels>,
 ) {
 	for event in events.read() {
 		match event {
@@ -237,17 +432,12 @@ pub fn connection_handler(
 						warn!(vessel_id=?id.0, ?client_id, "trying to share vessel \\w client, but we don't have it");
 						continue;
 					};
-					new_vessel_send.send(ToClients {
-						mode: SendMode::Direct(*client_id),
-						event: AddVessel {
-							vessel_id: *id,
-							sim_vessel: vessel.clone(),
-						},
-					});
+					sent_vessels.send(*client_id, *id, vessel, &mut new_vessel_send);
 				}
 			},
 			ServerEvent::ClientDisconnected { client_id, reason } => {
 				info!(?client_id, reason, "client disconnected");
+				sent_vessels.map.remove(client_id);
 				let maybe_entity = client_owned_entities.map.remove(client_id);
 				if let Some(entity) = maybe_entity {
 					cmds.entity(entity).despawn_recursive();
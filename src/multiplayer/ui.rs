@@ -201,6 +201,32 @@ pub fn groups_ui(ui: &mut Ui, world: &mut World) {
 }
 
 
+///Tells the user the server didn't accept their vessel
+#[cfg(feature="user_interface")]
+pub fn rejection_ui(
+	mut contexts: bevy_egui::EguiContexts,
+	rejected: Option<Res<super::RejectedVessel>>,
+	mut cmds: Commands,
+) {
+	let Some(rejected) = rejected else {return};
+	let Some(ctx) = contexts.try_ctx_mut() else {
+		// Primary window is missing, because it still is being initialized or has been closed
+		// This system can still run in those conditions, so just do nothing until other systems fix it
+		return;
+	};
+	
+	bevy_egui::egui::Window::new("Vessel rejected").resizable(false).show(ctx, |ui| {
+		ui.label("The server doesn't know these elements your vessel uses:");
+		for id in &rejected.0.unknown {
+			ui.label(RichText::new(id).font(FontId::monospace(FONT_SIZE)));
+		}
+		if ui.button("Close").clicked() {
+			cmds.remove_resource::<super::RejectedVessel>();
+		}
+	});
+}
+
+
 #[derive(Default)]
 pub enum Tab {
 	#[default]
@@ -250,5 +276,5 @@ pub fn debug_ui(
 			});
 		})
 	;
-	
+
 }
\ No newline at end of file
@@ -0,0 +1,449 @@
+/*!
+The race tracks vessels drive on.
+
+Tracks are described by [Definition] assets in [DEFINITION_DIR], which the user can choose from.
+There's also a [Definition::builtin] track made out of boxes, so there's something to drive on without any track assets.
+
+Only the track itself is handled here, racing on it is done by [crate::worldplay::race].
+*/
+
+use std::{
+	collections::BTreeMap,
+	path::PathBuf,
+	time::Duration,
+};
+
+use avian3d::prelude::*;
+use bevy::{
+	asset::AssetLoadFailedEvent,
+	prelude::*,
+	time::common_conditions::on_timer,
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::Deserialize;
+
+use crate::{element, worldplay::race};
+
+
+///Folder in the assets folder containing the track definitions
+pub const DEFINITION_DIR: &str = "tracks";
+///File extension of track definitions
+pub const DEFINITION_EXTENSION: &str = "track.ron";
+
+
+///Keeps the list of [Tracks] up to date, and spawns the [SelectedTrack]
+pub struct TrackPlugin;
+
+impl Plugin for TrackPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_plugins(RonAssetPlugin::<Definition>::new(&[DEFINITION_EXTENSION]))
+			.init_resource::<Tracks>()
+			.init_resource::<SelectedTrack>()
+			.insert_resource(CurrentTrack(Definition::builtin()))
+			.add_systems(Startup, scan_tracks)
+			.add_systems(Update, (
+				scan_tracks.run_if(on_timer(Duration::from_secs(1))),
+				log_load_errors,
+				spawn_track.after(scan_tracks),
+			));
+		
+		#[cfg(feature="user_interface")]
+		app.add_systems(Update, (
+			track_ui,
+			draw_spawn_points,
+		));
+	}
+}
+
+
+///Describes a track
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Definition {
+	///Name to show to the user
+	pub name: String,
+	///Path to a scene asset with the geometry of the track, which is also used for its collider.
+	/// E.g. `"local/track.glb#Scene0"`
+	#[serde(default)]
+	pub scene: Option<String>,
+	///Where the track is placed in the world
+	#[serde(default)]
+	pub transform: TrackTransform,
+	///Simple static boxes, added to the scene
+	#[serde(default)]
+	pub boxes: Vec<TrackBox>,
+	///Where vessels can start the race, relative to the track
+	#[serde(default)]
+	pub spawn_points: Vec<SpawnPoint>,
+	///In the order they should be driven through, relative to the track
+	#[serde(default)]
+	pub checkpoints: Vec<CheckpointDefinition>,
+	///Downwards acceleration
+	#[serde(default = "default_gravity")]
+	pub gravity: f32,
+	///Vessels falling below this height (relative to the track) get respawned
+	#[serde(default = "default_kill_height")]
+	pub kill_height: f32,
+}
+
+fn default_gravity() -> f32 {
+	15.
+}
+
+fn default_kill_height() -> f32 {
+	-50.
+}
+
+///Serializable form of the [Transform] of a track
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TrackTransform {
+	pub translation: Vec3,
+	///Euler angles around the X, Y and Z axes in degrees, see [EulerRot::XYZ]
+	pub rotation: Vec3,
+	pub scale: Vec3,
+}
+
+impl Default for TrackTransform {
+	fn default() -> Self {
+		Self {
+			translation: Vec3::ZERO,
+			rotation: Vec3::ZERO,
+			scale: Vec3::ONE,
+		}
+	}
+}
+
+impl TrackTransform {
+	pub fn transform(&self) -> Transform {
+		let [x, y, z] = self.rotation.to_array().map(f32::to_radians);
+		Transform {
+			translation: self.translation,
+			rotation: Quat::from_euler(EulerRot::XYZ, x, y, z),
+			scale: self.scale,
+		}
+	}
+	
+	///Puts something placed at `local` relative to the track in world space.
+	/// Only its position gets scaled, so vessels placed with it keep their size.
+	pub fn place(&self, local: Transform) -> Transform {
+		let track = self.transform();
+		Transform::from_translation(track.transform_point(local.translation))
+			.with_rotation(track.rotation * local.rotation)
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrackBox {
+	pub centre: Vec3,
+	pub size: Vec3,
+	///Rotation around the Z axis in degrees, positive makes it go up towards X+
+	#[serde(default)]
+	pub pitch: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpawnPoint {
+	pub position: Vec3,
+	///Rotation around the Y axis in degrees, 0 faces X+
+	#[serde(default)]
+	pub heading: f32,
+}
+
+impl SpawnPoint {
+	pub fn transform(&self) -> Transform {
+		Transform::from_translation(self.position)
+			.with_rotation(Quat::from_rotation_y(self.heading.to_radians()))
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CheckpointDefinition {
+	pub centre: Vec3,
+	pub size: Vec3,
+}
+
+impl Definition {
+	///A flat floor with some ramps, always available
+	pub fn builtin() -> Self {
+		let gate = |x: f32, z: f32, along_x: bool| CheckpointDefinition {
+			centre: Vec3::new(x, 2., z),
+			size: if along_x {Vec3::new(2., 8., 16.)} else {Vec3::new(16., 8., 2.)},
+		};
+		Self {
+			name: "Built-in".into(),
+			scene: None,
+			transform: TrackTransform::default(),
+			boxes: vec![
+				TrackBox {centre: Vec3::new(20., -3., 20.), size: Vec3::new(160., 2., 160.), pitch: 0.},
+				TrackBox {centre: Vec3::new(20., -2., 0.), size: Vec3::new(12., 1., 10.), pitch: 8.},
+				TrackBox {centre: Vec3::new(20., -2., 40.), size: Vec3::new(12., 1., 10.), pitch: -8.},
+			],
+			spawn_points: vec![
+				SpawnPoint {position: Vec3::new(-6., 0., 0.), heading: 0.},
+				SpawnPoint {position: Vec3::new(-6., 0., 6.), heading: 0.},
+				SpawnPoint {position: Vec3::new(-6., 0., -6.), heading: 0.},
+			],
+			checkpoints: vec![
+				gate(0., 0., true),
+				gate(40., 0., true),
+				gate(40., 40., false),
+				gate(0., 40., true),
+			],
+			gravity: default_gravity(),
+			kill_height: default_kill_height(),
+		}
+	}
+	
+	///Where the vessel in the given spawn slot should start, in world space.
+	/// Slots beyond the spawn points start further behind them.
+	pub fn spawn_transform(&self, slot: usize) -> Transform {
+		if self.spawn_points.is_empty() {
+			return self.transform.place(Transform::from_xyz(0., 0., slot as f32 * 6.));
+		}
+		let spawn_point = self.spawn_points[slot % self.spawn_points.len()].transform();
+		let row = (slot / self.spawn_points.len()) as f32;
+		self.transform.place(spawn_point * Transform::from_xyz(-row * 8., 0., 0.))
+	}
+	
+	///Whether `position` (in world space) is the centre of one of the checkpoints
+	pub fn is_checkpoint(&self, position: Vec3) -> bool {
+		self.checkpoints.iter().any(|checkpoint| {
+			let centre = self.transform.place(Transform::from_translation(checkpoint.centre)).translation;
+			centre.distance_squared(position) < 0.01
+		})
+	}
+	
+	///World space height below which vessels get respawned, that of the point [Definition::kill_height] above the track's origin
+	pub fn world_kill_height(&self) -> f32 {
+		self.transform.transform().transform_point(Vec3::Y * self.kill_height).y
+	}
+}
+
+
+///Handles to all the known track [Definition] files, by asset path
+#[derive(Resource, Default)]
+pub struct Tracks {
+	pub handles: BTreeMap<PathBuf, Handle<Definition>>,
+}
+
+///Asset path of the track to drive on, [None] is the [Definition::builtin] track
+#[derive(Resource, Default, PartialEq)]
+pub struct SelectedTrack(pub Option<PathBuf>);
+
+impl SelectedTrack {
+	///Short name that identifies the track, usable in file names
+	#[cfg(feature="user_interface")]
+	pub fn key(&self) -> String {
+		let Some(path) = &self.0 else {
+			return "builtin".into();
+		};
+		let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+		file_name.strip_suffix(&format!(".{DEFINITION_EXTENSION}")).unwrap_or(&file_name).to_string()
+	}
+}
+
+///The track that's currently spawned
+#[derive(Resource)]
+pub struct CurrentTrack(pub Definition);
+
+///Marks the entity with all the parts of the spawned track as children
+#[derive(Component)]
+pub struct TrackRoot;
+
+
+///Loads all definition files in [DEFINITION_DIR], and forgets about the ones that have been deleted
+pub fn scan_tracks(
+	mut tracks: ResMut<Tracks>,
+	assets: Res<AssetServer>,
+) {
+	let found = match element::find_asset_files(DEFINITION_DIR, DEFINITION_EXTENSION) {
+		Ok(found) => found,
+		Err(err) => {
+			warn_once!(dir=DEFINITION_DIR, %err, "could not read track definitions directory");
+			return;
+		},
+	};
+	
+	tracks.handles.retain(|path, _| found.contains(path));
+	for path in found {
+		tracks.handles.entry(path.clone()).or_insert_with(|| assets.load(path));
+	}
+}
+
+
+pub fn log_load_errors(
+	mut failed_events: EventReader<AssetLoadFailedEvent<Definition>>,
+) {
+	for event in failed_events.read() {
+		error!(path=%event.path, error=%event.error, "failed to load track definition");
+	}
+}
+
+
+///Respawns the track when another one gets selected, or the selected one changes
+pub fn spawn_track(
+	selected: Res<SelectedTrack>,
+	tracks: Res<Tracks>,
+	definitions: Res<Assets<Definition>>,
+	mut asset_events: EventReader<AssetEvent<Definition>>,
+	existing: Query<Entity, With<TrackRoot>>,
+	mut current: ResMut<CurrentTrack>,
+	mut gravity: ResMut<Gravity>,
+	mut cmds: Commands,
+	assets: Res<AssetServer>,
+	#[cfg(feature="user_interface")]
+	mut meshes: ResMut<Assets<Mesh>>,
+	#[cfg(feature="user_interface")]
+	mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+	let handle = selected.0.as_ref().and_then(|path| tracks.handles.get(path));
+	let selected_modified = asset_events.read().any(|event| match event {
+		AssetEvent::Added {id} | AssetEvent::Modified {id} => handle.is_some_and(|handle| handle.id() == *id),
+		_ => false,
+	});
+	if !selected.is_changed() && !selected_modified {return}
+	
+	let definition = match handle {
+		Some(handle) => match definitions.get(handle) {
+			Some(definition) => definition.clone(),
+			// gets spawned once it's loaded
+			None => return,
+		},
+		None => {
+			if let Some(path) = &selected.0 {
+				warn!(?path, "selected track doesn't exist, using the built-in one");
+			}
+			Definition::builtin()
+		},
+	};
+	
+	for entity in &existing {
+		cmds.entity(entity).despawn_recursive();
+	}
+	info!(name=definition.name, "spawning track");
+	
+	let root = cmds.spawn((
+		TrackRoot,
+		Name::new(format!("World/Track {}", definition.name)),
+		definition.transform.transform(),
+		Visibility::default(),
+	)).id();
+	
+	if let Some(scene) = &definition.scene {
+		cmds.spawn((
+			SceneRoot(assets.load(scene.clone())),
+			Transform::default(),
+			ColliderConstructorHierarchy::new(ColliderConstructor::TrimeshFromMeshWithConfig(
+				TrimeshFlags::MERGE_DUPLICATE_VERTICES
+				| TrimeshFlags::DELETE_DUPLICATE_TRIANGLES
+				| TrimeshFlags::DELETE_DEGENERATE_TRIANGLES
+				| TrimeshFlags::FIX_INTERNAL_EDGES
+				| TrimeshFlags::DELETE_BAD_TOPOLOGY_TRIANGLES
+			)),
+			CollisionMargin(0.002), // should help with stability around trimeshes
+			RigidBody::Static,
+			Name::new("Track Scene"),
+		)).set_parent(root);
+	}
+	
+	#[cfg(feature="user_interface")]
+	let material = materials.add(StandardMaterial {
+		base_color: Color::srgb(0.4, 0.45, 0.4),
+		perceptual_roughness: 0.9,
+		..default()
+	});
+	for track_box in &definition.boxes {
+		let size = track_box.size;
+		let mut entity = cmds.spawn((
+			Transform::from_translation(track_box.centre)
+				.with_rotation(Quat::from_rotation_z(track_box.pitch.to_radians())),
+			Collider::cuboid(size.x, size.y, size.z),
+			RigidBody::Static,
+			Name::new("Track Box"),
+		));
+		entity.set_parent(root);
+		#[cfg(feature="user_interface")]
+		entity.insert((
+			Mesh3d(meshes.add(Cuboid::from_size(size))),
+			MeshMaterial3d(material.clone()),
+		));
+	}
+	
+	for (index, checkpoint) in definition.checkpoints.iter().enumerate() {
+		let size = checkpoint.size;
+		cmds.spawn((
+			race::Checkpoint {index},
+			Transform::from_translation(checkpoint.centre),
+			Collider::cuboid(size.x, size.y, size.z),
+			Sensor,
+			Name::new(format!("Checkpoint {index}")),
+		)).set_parent(root);
+	}
+	
+	gravity.0 = -Vec3::Y * definition.gravity;
+	current.0 = definition;
+}
+
+
+#[cfg(feature="user_interface")]
+pub fn draw_spawn_points(
+	current: Res<CurrentTrack>,
+	mut gizmos: Gizmos,
+) {
+	use bevy::color::palettes::css;
+	
+	for spawn_point in &current.0.spawn_points {
+		let transform = current.0.transform.place(spawn_point.transform());
+		gizmos.circle(Isometry3d::new(transform.translation, transform.rotation * Quat::from_rotation_x(core::f32::consts::FRAC_PI_2)), 0.5, css::AQUA);
+		gizmos.arrow(transform.translation, transform.translation + transform.rotation * Vec3::X, css::AQUA);
+	}
+}
+
+
+#[cfg(feature="user_interface")]
+pub fn track_ui(
+	mut contexts: bevy_egui::EguiContexts,
+	tracks: Res<Tracks>,
+	definitions: Res<Assets<Definition>>,
+	mut selected: ResMut<SelectedTrack>,
+	current: Res<CurrentTrack>,
+	race_state: Option<Res<State<race::RaceState>>>,
+	client: Res<bevy_replicon::prelude::RepliconClient>,
+) {
+	use bevy_egui::egui;
+	let Some(ctx) = contexts.try_ctx_mut() else {
+		// Primary window is missing, because it still is being initialized or has been closed
+		// This system can still run in those conditions, so just do nothing until other systems fix it
+		return;
+	};
+	
+	// changing the track in the middle of a race would mess up its checkpoints
+	let racing = race_state.is_some_and(|state| matches!(state.get(), race::RaceState::Countdown | race::RaceState::Racing));
+	// the server simulates the vessels, so it decides which track they drive on, see [crate::multiplayer::TrackSelected]
+	let connected = client.is_connected();
+	
+	egui::Window::new("Track").resizable(false).default_open(false).show(ctx, |ui| {
+		ui.add_enabled_ui(!racing && !connected, |ui| {
+			let mut choice = selected.0.clone();
+			egui::ComboBox::from_id_salt("track")
+				.selected_text(&current.0.name)
+				.show_ui(ui, |ui| {
+					ui.selectable_value(&mut choice, None, Definition::builtin().name);
+					for (path, handle) in &tracks.handles {
+						let name = match definitions.get(handle) {
+							Some(definition) => definition.name.clone(),
+							None => path.display().to_string(),
+						};
+						ui.selectable_value(&mut choice, Some(path.clone()), name);
+					}
+				});
+			selected.set_if_neq(SelectedTrack(choice));
+		});
+		if connected {
+			ui.label("The server picks the track");
+		} else if racing {
+			ui.label("Can't change the track while racing");
+		}
+	});
+}
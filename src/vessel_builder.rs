@@ -9,17 +9,21 @@ Vessel pipeline:
 
 */
 
+use std::collections::{BTreeMap, VecDeque};
+
 use avian3d::prelude::Collider;
-use bevy::{asset::Assets, math::{Quat, Vec3}, prelude::{
-	Commands, Res, ResMut, Transform
-}};
+use bevy::{asset::Assets, log::{debug, error, warn}, math::{IVec3, Mat3, Quat, Vec3}, prelude::{
+	Commands, Res, ResMut
+}, utils::{HashMap, HashSet}};
 
 use crate::{
-	editor::misc::CreationData,
+	editor::{grid, misc::CreationData},
+	element,
 	worldplay::{
+		parts::Part,
 		user::UserVesselId,
 		vessel::{
-			SimVessel, VesselProperties
+			self, MassProperties, SimVessel, VesselProperties
 		}
 	}
 };
@@ -30,34 +34,467 @@ pub fn build_vessel_system(
 	mut vessels: ResMut<Assets<SimVessel>>,
 	mut cmds: Commands,
 ) {
+	match validate(&creation) {
+		Err(problem) if problem.blocks_play() => {
+			// the play button should've prevented getting here
+			error!(%problem, "can't build vessel");
+			return;
+		},
+		Err(problem) => warn!(%problem, "building vessel anyway"),
+		Ok(()) => {},
+	}
+	
 	let sim = build_sim_vessel(&creation);
-	let id = uuid::Uuid::new_v4();
-	vessels.insert(id, sim);
-	cmds.insert_resource(UserVesselId(id.into()));
+	let id = vessel::Id::of(&sim);
+	// playing the same vessel again just overwrites it with the same data
+	vessels.insert(id.0, sim);
+	cmds.insert_resource(UserVesselId(id));
 }
 
 
 pub fn build_sim_vessel(
 	creation: &CreationData,
 ) -> SimVessel {
+	// vessels get identified by a hash of their data, so identical vessels should also have identical data
+	let creation = &creation.canonical();
+	
 	let mut graphics = Vec::new();
 	let mut collider_parts: Vec<(Vec3, Quat, Collider)> = Vec::new();
+	let mut parts = Vec::new();
+	
+	// cuboids fill their cells, so they can be merged into bigger boxes
+	let mut cuboid_cells = HashSet::new();
 	
 	for object in &creation.objects {
-		let pos = object.pos.0;
-		let transform = Transform::from_translation(pos.as_vec3());
+		let transform = object.pos.transform(&object.element, object.orientation);
 		
+		if object.element.collider_shape == element::Shape::Cuboid {
+			let size = object.orientation.rotate_size(object.element.size);
+			cuboid_cells.extend(grid::cells(object.pos.0, size));
+		} else {
+			collider_parts.push((
+				transform.translation,
+				transform.rotation,
+				object.element.collider.clone(),
+			));
+		}
+		graphics.push((object.element.id.clone(), transform));
+		if object.element.kind != element::Kind::Passive {
+			parts.push(Part {
+				kind: object.element.kind.clone(),
+				transform,
+			});
+		}
+	}
+	
+	let boxes = merge_cells(&cuboid_cells);
+	debug!(cells = cuboid_cells.len(), boxes = boxes.len(), "merged cuboid colliders");
+	for (min, size) in boxes {
+		let size = size.as_vec3();
 		collider_parts.push((
-			pos.as_vec3(),
-			Quat::default(),
-			object.element.collider.clone(),
+			min.as_vec3() + size / 2.,
+			Quat::IDENTITY,
+			Collider::cuboid(size.x, size.y, size.z),
 		));
-		graphics.push((object.element.id.clone(), transform));
 	}
 	
 	SimVessel {
 		graphics,
 		collider: Collider::compound(collider_parts),
-		physics_properties: VesselProperties::default(),
+		physics_properties: vessel_properties(creation),
+		mass_properties: mass_properties(creation),
+		parts,
+	}
+}
+
+/**
+Covers the `cells` with as few boxes as reasonably possible, returned as (lowest corner, size).
+
+Greedily grows boxes from the lowest remaining cell, first along Z, then Y, then X,
+which is a lot fewer colliders than one per cell for big blocky vessels.
+*/
+pub fn merge_cells(
+	cells: &HashSet<IVec3>,
+) -> Vec<(IVec3, IVec3)> {
+	let mut sorted = cells.iter().copied().collect::<Vec<_>>();
+	sorted.sort_by_key(|cell| (cell.x, cell.y, cell.z));
+	
+	let mut covered = HashSet::new();
+	let mut boxes = Vec::new();
+	let free = |cell: &IVec3, covered: &HashSet<IVec3>| cells.contains(cell) && !covered.contains(cell);
+	
+	for start in sorted {
+		if covered.contains(&start) {continue}
+		
+		let mut size = IVec3::ONE;
+		while free(&(start + IVec3::Z * size.z), &covered) {
+			size.z += 1;
+		}
+		while grid::cells(start + IVec3::Y * size.y, size.with_y(1)).all(|cell| free(&cell, &covered)) {
+			size.y += 1;
+		}
+		while grid::cells(start + IVec3::X * size.x, size.with_x(1)).all(|cell| free(&cell, &covered)) {
+			size.x += 1;
+		}
+		
+		covered.extend(grid::cells(start, size));
+		boxes.push((start, size));
+	}
+	
+	boxes
+}
+
+
+///Adds up the [crate::element::Properties] of all objects on top of the [VesselProperties::default()].
+/// Heavier vessels don't need to be handled here, the physics already makes them accelerate and turn slower.
+pub fn vessel_properties(
+	creation: &CreationData,
+) -> VesselProperties {
+	let mut properties = VesselProperties::default();
+	for object in &creation.objects {
+		let element = &object.element.properties;
+		properties.control_torque += element.torque;
+		properties.side_friction += element.grip;
+		properties.drag += element.drag;
+	}
+	properties
+}
+
+
+///Something wrong with a creation that would make it a bad vessel
+#[derive(Debug, Clone)]
+pub enum Problem {
+	///There are no objects
+	Empty,
+	///Not all objects are connected to each other
+	Disconnected {
+		///Indices of the objects of every group that's connected, biggest first
+		islands: Vec<Vec<usize>>,
+	},
+}
+
+impl Problem {
+	///Whether it's impossible to turn the creation into a vessel, instead of just being a bad idea
+	pub fn blocks_play(&self) -> bool {
+		matches!(self, Self::Empty)
+	}
+}
+
+impl std::fmt::Display for Problem {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Empty => write!(f, "the creation is empty"),
+			Self::Disconnected {islands} => write!(f, "the creation consists of {} disconnected parts", islands.len()),
+		}
+	}
+}
+
+pub fn validate(
+	creation: &CreationData,
+) -> Result<(), Problem> {
+	if creation.objects.is_empty() {
+		return Err(Problem::Empty);
+	}
+	let islands = islands(creation);
+	if islands.len() > 1 {
+		return Err(Problem::Disconnected {islands});
+	}
+	Ok(())
+}
+
+///Groups the objects by which ones are connected through their faces, biggest group first
+pub fn islands(
+	creation: &CreationData,
+) -> Vec<Vec<usize>> {
+	let mut owners = HashMap::new();
+	for (index, object) in creation.objects.iter().enumerate() {
+		let size = object.orientation.rotate_size(object.element.size);
+		for cell in grid::cells(object.pos.0, size) {
+			owners.insert(cell, index);
+		}
+	}
+	
+	let mut visited = vec![false; creation.objects.len()];
+	let mut islands = Vec::new();
+	for start in 0..creation.objects.len() {
+		if visited[start] {continue}
+		visited[start] = true;
+		let mut island = Vec::new();
+		let mut todo = VecDeque::from([start]);
+		
+		while let Some(index) = todo.pop_front() {
+			island.push(index);
+			let object = &creation.objects[index];
+			let size = object.orientation.rotate_size(object.element.size);
+			for cell in grid::cells(object.pos.0, size) {
+				for direction in [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z] {
+					let Some(&neighbour) = owners.get(&(cell + direction)) else {continue};
+					if visited[neighbour] {continue}
+					visited[neighbour] = true;
+					todo.push_back(neighbour);
+				}
+			}
+		}
+		islands.push(island);
+	}
+	
+	islands.sort_by_key(|island| std::cmp::Reverse(island.len()));
+	islands
+}
+
+
+///Summary of what a creation will be like as a vessel
+#[derive(Default, Debug, Clone)]
+pub struct Stats {
+	pub mass: MassProperties,
+	///Lowest and highest corner of the creation, [None] when it's empty
+	pub bounds: Option<(IVec3, IVec3)>,
+	///Amount of objects by element name
+	pub counts: BTreeMap<String, usize>,
+	pub object_count: usize,
+	pub properties: VesselProperties,
+	///Force of all the [element::Kind::Thruster]s added up, whichever way they point
+	pub thruster_force: f32,
+}
+
+pub fn creation_stats(
+	creation: &CreationData,
+) -> Stats {
+	let mut stats = Stats::default();
+	
+	for object in &creation.objects {
+		let size = object.orientation.rotate_size(object.element.size);
+		let (min, max) = (object.pos.0, object.pos.0 + size);
+		stats.bounds = Some(match stats.bounds {
+			Some((old_min, old_max)) => (old_min.min(min), old_max.max(max)),
+			None => (min, max),
+		});
+		*stats.counts.entry(object.element.name.clone()).or_default() += 1;
+		if let element::Kind::Thruster {force} = object.element.kind {
+			stats.thruster_force += force;
+		}
+	}
+	stats.object_count = creation.objects.len();
+	stats.properties = vessel_properties(creation);
+	stats.mass = mass_properties(creation);
+	
+	stats
+}
+
+///Adds up the mass of all objects.
+/// Every object is treated as a solid box filling its cells, whatever its shape.
+pub fn mass_properties(
+	creation: &CreationData,
+) -> MassProperties {
+	// (center, size, mass) of every object
+	let mut parts = Vec::new();
+	let mut total_mass = 0.;
+	let mut center_of_mass = Vec3::ZERO;
+	
+	for object in &creation.objects {
+		let transform = object.pos.transform(&object.element, object.orientation);
+		let size = object.orientation.rotate_size(object.element.size).as_vec3();
+		let mass = object.element.mass;
+		
+		parts.push((transform.translation, size, mass));
+		total_mass += mass;
+		center_of_mass += transform.translation * mass;
+	}
+	if total_mass > 0. {
+		center_of_mass /= total_mass;
+	}
+	
+	let mut inertia = Mat3::ZERO;
+	for (center, size, mass) in parts {
+		let squared = size * size;
+		let own = Vec3::new(squared.y + squared.z, squared.x + squared.z, squared.x + squared.y) * mass / 12.;
+		// parallel axis theorem
+		let offset = center - center_of_mass;
+		let shifted = (Mat3::IDENTITY * offset.length_squared() - outer_product(offset, offset)) * mass;
+		inertia += Mat3::from_diagonal(own) + shifted;
+	}
+	
+	MassProperties {
+		mass: total_mass,
+		center_of_mass,
+		inertia,
+	}
+}
+
+fn outer_product(a: Vec3, b: Vec3) -> Mat3 {
+	Mat3::from_cols(a * b.x, a * b.y, a * b.z)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	
+	use avian3d::prelude::*;
+	use bevy::{prelude::*, time::TimeUpdateStrategy};
+	
+	use super::*;
+	
+	///Checks that every cell is covered by exactly one box, and the boxes don't cover anything else
+	fn assert_exact_cover(cells: &HashSet<IVec3>, boxes: &[(IVec3, IVec3)]) {
+		let mut covered = HashSet::new();
+		for &(min, size) in boxes {
+			assert!(size.min_element() >= 1, "box at {min} has size {size}");
+			for cell in grid::cells(min, size) {
+				assert!(cells.contains(&cell), "box at {min} with size {size} covers {cell}, which isn't in the cells");
+				assert!(covered.insert(cell), "{cell} is covered by multiple boxes");
+			}
+		}
+		assert_eq!(covered.len(), cells.len(), "not all cells are covered");
+	}
+	
+	///Deterministic pseudo random cells in a box of `size`, about half of them filled
+	fn random_cells(seed: u64, size: IVec3) -> HashSet<IVec3> {
+		let mut state = seed;
+		grid::cells(IVec3::ZERO, size)
+			.filter(|_| {
+				// splitmix64
+				state = state.wrapping_add(0x9E3779B97F4A7C15);
+				let mut z = state;
+				z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+				z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+				(z ^ (z >> 31)) & 1 == 0
+			})
+			.collect()
+	}
+	
+	fn hollow_box(min: IVec3, size: IVec3) -> HashSet<IVec3> {
+		let max = min + size - IVec3::ONE;
+		grid::cells(min, size)
+			.filter(|cell| cell.cmpeq(min).any() || cell.cmpeq(max).any())
+			.collect()
+	}
+	
+	#[test]
+	fn merge_cells_covers_random_cells() {
+		for seed in 0..20 {
+			let cells = random_cells(seed, IVec3::new(8, 5, 7));
+			assert_exact_cover(&cells, &merge_cells(&cells));
+		}
+	}
+	
+	#[test]
+	fn merge_cells_covers_hollow_box() {
+		let cells = hollow_box(IVec3::new(-3, 2, -1), IVec3::new(6, 5, 7));
+		let boxes = merge_cells(&cells);
+		assert_exact_cover(&cells, &boxes);
+		assert!(boxes.len() < cells.len() / 4, "{} boxes for {} cells", boxes.len(), cells.len());
+	}
+	
+	#[test]
+	fn merge_cells_merges_l_shape() {
+		let cells = grid::cells(IVec3::ZERO, IVec3::new(6, 1, 2))
+			.chain(grid::cells(IVec3::new(0, 0, 2), IVec3::new(2, 1, 4)))
+			.collect();
+		let boxes = merge_cells(&cells);
+		assert_exact_cover(&cells, &boxes);
+		assert_eq!(boxes.len(), 2);
+	}
+	
+	#[test]
+	fn merge_cells_without_cells() {
+		assert!(merge_cells(&HashSet::new()).is_empty());
+	}
+	
+	
+	///A row of `count` objects of the element along Z
+	fn row(element: &element::Ref, count: i32) -> CreationData {
+		let mut creation = CreationData::new();
+		for z in 0..count {
+			creation.objects.push(crate::editor::object::Object {
+				element: element.clone(),
+				pos: IVec3::new(0, 0, z * element.size.z).into(),
+				orientation: default(),
+			});
+		}
+		creation
+	}
+	
+	#[test]
+	fn vessel_properties_add_up() {
+		let catalogue = element::Catalogue::shipped();
+		let fin = catalogue.get("fin").expect("fin should be shipped");
+		let base = VesselProperties::default();
+		
+		let one = vessel_properties(&row(&fin, 1));
+		let three = vessel_properties(&row(&fin, 3));
+		assert!(one.side_friction > base.side_friction);
+		assert!(three.side_friction > one.side_friction);
+		assert!((three.side_friction - base.side_friction - 3. * fin.properties.grip).abs() < 1e-4);
+		assert!((three.control_torque - base.control_torque - 3. * fin.properties.torque).abs() < 1e-4);
+		
+		let block = catalogue.get("block").expect("block should be shipped");
+		let blocks = vessel_properties(&row(&block, 3));
+		assert_eq!(blocks.side_friction, base.side_friction);
+	}
+	
+	
+	///Average time of a physics step with some vessels with the `collider` resting on the ground
+	fn step_time(collider: &Collider) -> Duration {
+		const WARMUP: u32 = 30;
+		const STEPS: u32 = 200;
+		
+		let mut app = App::new();
+		app.add_plugins((
+			MinimalPlugins,
+			TransformPlugin,
+			HierarchyPlugin,
+			bevy::asset::AssetPlugin::default(),
+			bevy::scene::ScenePlugin,
+			PhysicsPlugins::default(),
+		))
+			.init_resource::<Assets<Mesh>>()
+			// one physics step every update
+			.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(1. / 64.)));
+		
+		app.world_mut().spawn((
+			RigidBody::Static,
+			Collider::cuboid(200., 1., 200.),
+			Transform::from_xyz(0., -0.5, 0.),
+		));
+		for x in 0..3 {
+			for z in 0..3 {
+				app.world_mut().spawn((
+					RigidBody::Dynamic,
+					collider.clone(),
+					Transform::from_xyz(x as f32 * 30., 0.1, z as f32 * 30.),
+				));
+			}
+		}
+		
+		for _ in 0..WARMUP {
+			app.update();
+		}
+		let start = Instant::now();
+		for _ in 0..STEPS {
+			app.update();
+		}
+		start.elapsed() / STEPS
+	}
+	
+	///Compares the physics step time of a compound with a collider per cell with a merged one.
+	/// Run with `cargo test --release bench_merged_collider -- --ignored --nocapture`
+	#[test]
+	#[ignore = "benchmark"]
+	fn bench_merged_collider() {
+		// like the hull of a big vessel
+		let cells = hollow_box(IVec3::ZERO, IVec3::new(16, 4, 10));
+		let cube = |min: IVec3, size: IVec3| {
+			let size = size.as_vec3();
+			(min.as_vec3() + size / 2., Quat::IDENTITY, Collider::cuboid(size.x, size.y, size.z))
+		};
+		let per_cell = Collider::compound(cells.iter().map(|&cell| cube(cell, IVec3::ONE)).collect());
+		let boxes = merge_cells(&cells);
+		let merged = Collider::compound(boxes.iter().map(|&(min, size)| cube(min, size)).collect());
+		
+		let per_cell_time = step_time(&per_cell);
+		let merged_time = step_time(&merged);
+		println!("per cell: {} colliders, {per_cell_time:?} per step", cells.len());
+		println!("merged: {} colliders, {merged_time:?} per step", boxes.len());
 	}
 }
@@ -11,6 +11,13 @@ use derive_more::{From, Into};
 
 pub mod vessel;
 pub mod user;
+pub mod parts;
+pub mod race;
+pub mod respawn;
+#[cfg(feature="user_interface")]
+pub mod replay;
+#[cfg(feature="user_interface")]
+pub mod vessel_mesh;
 
 pub struct GameplayPlugin;
 
@@ -19,19 +26,64 @@ impl Plugin for GameplayPlugin {
 		app.init_state::<WorldState>();
 		app.add_computed_state::<WorldLoaded>();
 		app.enable_state_scoped_entities::<WorldLoaded>();
+		app.add_sub_state::<race::RaceState>();
+		app.enable_state_scoped_entities::<race::RaceState>();
 		
 		app.init_resource::<user::CameraSettings>();
+		app.init_resource::<race::RaceSettings>();
+		app.init_resource::<race::RaceProgress>();
+		#[cfg(feature="user_interface")]
+		app.init_resource::<vessel_mesh::MergeVesselMeshes>();
 		
 		app.init_asset::<vessel::SimVessel>();
 		app.register_asset_reflect::<vessel::SimVessel>();
 		app.register_type::<vessel::Id>();
+		app.add_event::<respawn::Respawn>();
+		app.add_event::<race::LapFinished>();
 		
 		app.add_systems(OnEnter(WorldState::Foreground), (
 			user::spawn_user,
 		));
+		app.add_systems(OnEnter(race::RaceState::Countdown), (
+			race::clear_respawn_points,
+			race::start_countdown,
+		));
+		app.add_systems(OnEnter(race::RaceState::FreeDrive), race::clear_respawn_points);
+		#[cfg(feature="user_interface")]
+		{
+			app.init_resource::<replay::Recording>();
+			app.init_resource::<replay::BestReplay>();
+			app.init_resource::<replay::GhostMaterial>();
+			app.add_systems(OnEnter(race::RaceState::Countdown), replay::load_best_replay);
+			app.add_systems(OnEnter(race::RaceState::Racing), replay::clear_recording);
+			app.add_systems(Update, (
+					replay::finish_lap.after(race::pass_checkpoints),
+					replay::record,
+					replay::spawn_ghost,
+					replay::move_ghost,
+				)
+				.chain()
+				.run_if(in_state(race::RaceState::Racing))
+			);
+		}
 		app.add_systems(Update, (
+				race::update_countdown.run_if(in_state(race::RaceState::Countdown)),
+				race::pass_checkpoints.run_if(in_state(race::RaceState::Racing)),
+				#[cfg(feature="user_interface")]
+				race::draw_checkpoints,
+				#[cfg(feature="user_interface")]
+				race::race_ui,
+			)
+			.run_if(in_state(WorldState::Foreground))
+		);
+		app.add_systems(Update, (
+				#[cfg(feature="user_interface")]
+				user::read_user_input
+					.before(vessel::move_vessel)
+					// the vessel has to wait for the start
+					.run_if(not(in_state(race::RaceState::Countdown))),
 				#[cfg(feature="user_interface")]
-				user::read_user_input.before(vessel::move_vessel),
+				respawn::reset_input.before(respawn::respawn_vessels),
 				user::update_camera,
 				#[cfg(feature="user_interface")]
 				user::camera_ui,
@@ -40,7 +92,17 @@ impl Plugin for GameplayPlugin {
 		);
 		app.add_systems(Update, (
 				vessel::spawn_vessels.before(avian3d::prelude::PhysicsSet::Prepare),
+				respawn::respawn_vessels
+					.after(vessel::spawn_vessels)
+					.before(avian3d::prelude::PhysicsSet::Prepare),
+				// clients don't simulate vessels, the server respawns them
+				respawn::kill_height
+					.before(respawn::respawn_vessels)
+					.run_if(not(bevy_replicon::prelude::client_connected)),
 				vessel::move_vessel.before(avian3d::prelude::PhysicsSet::StepSimulation),
+				parts::apply_part_forces
+					.after(vessel::move_vessel)
+					.before(avian3d::prelude::PhysicsSet::StepSimulation),
 			)
 			.run_if(in_state(WorldLoaded))
 		);
@@ -0,0 +1,81 @@
+/*!
+Parts of a vessel that apply forces at their own location, based on their [element::Kind].
+*/
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::element;
+use super::vessel::Control;
+
+
+///A functional part of a [super::vessel::SimVessel]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Part {
+	pub kind: element::Kind,
+	///Relative to the vessel
+	pub transform: Transform,
+}
+
+///The functional parts of a spawned vessel
+#[derive(Component, Debug, Clone)]
+pub struct Parts(pub Vec<Part>);
+
+
+///Applies the forces of all the [Parts].
+/// Should run after [super::vessel::move_vessel], which resets the forces.
+pub fn apply_part_forces(
+	mut vessels: Query<(
+		Entity,
+		&Parts,
+		&Control,
+		&Position,
+		&Rotation,
+		&ComputedCenterOfMass,
+		&LinearVelocity,
+		&AngularVelocity,
+		&mut ExternalForce,
+	)>,
+	sensors: Query<(), With<Sensor>>,
+	spatial_query: SpatialQuery,
+) {
+	for (entity, parts, control, position, rotation, com, vel, rot_vel, mut force) in &mut vessels {
+		let world_com = position.0 + rotation.0 * com.0;
+		let ignore_self = SpatialQueryFilter::from_excluded_entities([entity]);
+		
+		for part in &parts.0 {
+			let point = position.0 + rotation.0 * part.transform.translation;
+			let part_rotation = rotation.0 * part.transform.rotation;
+			let point_vel = vel.0 + rot_vel.0.cross(point - world_com);
+			
+			match part.kind {
+				element::Kind::Passive => {},
+				element::Kind::Thruster {force: thrust} => {
+					let direction = part_rotation * Vec3::X;
+					force.apply_force_at_point(direction * thrust * control.0.y, point, world_com);
+				},
+				element::Kind::Wheel {suspension_length, stiffness, damping, grip} => {
+					let up = rotation.0 * Vec3::Y;
+					// spatial queries also hit sensors, like checkpoints, which aren't ground
+					let not_sensor = |entity| !sensors.contains(entity);
+					let Some(hit) = spatial_query.cast_ray_predicate(point, rotation.0 * Dir3::NEG_Y, suspension_length, true, &ignore_self, &not_sensor) else {continue};
+					
+					let compression = suspension_length - hit.distance;
+					let compression_speed = -point_vel.dot(up);
+					let suspension = (compression * stiffness + compression_speed * damping).max(0.);
+					
+					let side = rotation.0 * Vec3::Z;
+					let sliding = point_vel.dot(side);
+					
+					force.apply_force_at_point(up * suspension - side * sliding * grip, point, world_com);
+				},
+				element::Kind::Fin {lift} => {
+					let normal = part_rotation * Vec3::Y;
+					let through = point_vel.dot(normal);
+					force.apply_force_at_point(-normal * through * lift, point, world_com);
+				},
+			}
+		}
+	}
+}
@@ -0,0 +1,277 @@
+/*!
+Racing the locally controlled vessel through [Checkpoint]s for a number of laps.
+
+Checkpoints have to be passed in order, passing the first one again after all others completes a lap.
+*/
+
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::*;
+
+
+///What part of a race the local user is in
+#[derive(SubStates, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[source(WorldState = WorldState::Foreground)]
+pub enum RaceState {
+	///Driving around without an objective
+	#[default]
+	FreeDrive,
+	///Waiting for the start, the vessel can't be controlled yet
+	Countdown,
+	Racing,
+	///Showing the results
+	Finished,
+}
+
+
+///A trigger volume vessels have to pass through, spawned by the [crate::track]
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Checkpoint {
+	///Order in which they have to be passed, 0 is the start/finish line
+	pub index: usize,
+}
+
+
+#[derive(Resource)]
+pub struct RaceSettings {
+	pub laps: u32,
+	pub countdown: Duration,
+}
+
+impl Default for RaceSettings {
+	fn default() -> Self {
+		Self {
+			laps: 3,
+			countdown: Duration::from_secs(3),
+		}
+	}
+}
+
+
+///The local vessel completed a lap
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LapFinished {
+	// only the replays need it, which are part of the user interface
+	#[cfg_attr(not(feature="user_interface"), allow(dead_code))]
+	pub lap_time: Duration,
+}
+
+
+///Progress and timing of the current or last race
+#[derive(Resource, Default, Debug)]
+pub struct RaceProgress {
+	///Index of the [Checkpoint] that has to be passed next
+	pub next_checkpoint: usize,
+	///When the countdown started
+	pub countdown_start: Duration,
+	///When the race (and so the first lap) started
+	pub start: Duration,
+	pub lap_start: Duration,
+	pub lap_times: Vec<Duration>,
+	///How long the whole race took, once it's finished
+	pub total: Option<Duration>,
+}
+
+#[cfg(feature="user_interface")]
+impl RaceProgress {
+	///The lap currently being driven, starting at 1
+	pub fn lap(&self) -> usize {
+		self.lap_times.len() + 1
+	}
+	
+	pub fn best_lap(&self) -> Option<Duration> {
+		self.lap_times.iter().min().copied()
+	}
+}
+
+
+pub fn start_countdown(
+	mut progress: ResMut<RaceProgress>,
+	mut local: Query<(Entity, &mut vessel::Control, Option<&respawn::SpawnSlot>), With<user::LocallyControlled>>,
+	track: Res<crate::track::CurrentTrack>,
+	mut respawns: EventWriter<respawn::Respawn>,
+	time: Res<Time>,
+) {
+	*progress = RaceProgress {
+		countdown_start: time.elapsed(),
+		..default()
+	};
+	for (entity, mut control, slot) in &mut local {
+		// otherwise it'd keep doing whatever it was doing when the countdown started
+		control.0 = Vec2::ZERO;
+		respawns.send(respawn::Respawn {
+			entity,
+			transform: respawn::respawn_transform(slot, None, &track),
+		});
+	}
+}
+
+
+///Resetting should go to the start again instead of the last checkpoint of an earlier race
+pub fn clear_respawn_points(
+	local: Query<Entity, (With<user::LocallyControlled>, With<respawn::RespawnPoint>)>,
+	mut cmds: Commands,
+) {
+	for entity in &local {
+		cmds.entity(entity).remove::<respawn::RespawnPoint>();
+	}
+}
+
+
+pub fn update_countdown(
+	mut progress: ResMut<RaceProgress>,
+	checkpoints: Query<(), With<Checkpoint>>,
+	settings: Res<RaceSettings>,
+	time: Res<Time>,
+	mut next_state: ResMut<NextState<RaceState>>,
+) {
+	if time.elapsed() < progress.countdown_start + settings.countdown {return}
+	
+	progress.start = time.elapsed();
+	progress.lap_start = time.elapsed();
+	// starting counts as passing the start/finish line
+	progress.next_checkpoint = 1 % checkpoints.iter().len().max(1);
+	next_state.set(RaceState::Racing);
+}
+
+
+pub fn pass_checkpoints(
+	mut collisions: EventReader<CollisionStarted>,
+	checkpoints: Query<(&Checkpoint, &GlobalTransform)>,
+	local: Query<&Rotation, With<user::LocallyControlled>>,
+	mut cmds: Commands,
+	mut progress: ResMut<RaceProgress>,
+	settings: Res<RaceSettings>,
+	time: Res<Time>,
+	mut next_state: ResMut<NextState<RaceState>>,
+	mut laps: EventWriter<LapFinished>,
+) {
+	for CollisionStarted(a, b) in collisions.read() {
+		let (vessel, (checkpoint, checkpoint_transform)) = match (checkpoints.get(*a), checkpoints.get(*b)) {
+			(Ok(checkpoint), _) if local.contains(*b) => (*b, checkpoint),
+			(_, Ok(checkpoint)) if local.contains(*a) => (*a, checkpoint),
+			_ => continue,
+		};
+		if checkpoint.index != progress.next_checkpoint {continue}
+		
+		// resetting puts it back here, facing the way it went through
+		let rotation = local.get(vessel).expect("vessel should be local").0;
+		cmds.entity(vessel).insert(respawn::RespawnPoint(
+			Transform::from_translation(checkpoint_transform.translation())
+				.with_rotation(respawn::upright(rotation))
+		));
+		
+		progress.next_checkpoint = (checkpoint.index + 1) % checkpoints.iter().len();
+		if checkpoint.index != 0 {continue}
+		
+		let now = time.elapsed();
+		let lap_time = now - progress.lap_start;
+		progress.lap_times.push(lap_time);
+		progress.lap_start = now;
+		info!(lap=progress.lap_times.len(), ?lap_time, "finished lap");
+		laps.send(LapFinished {lap_time});
+		
+		if progress.lap_times.len() >= settings.laps as usize {
+			progress.total = Some(now - progress.start);
+			next_state.set(RaceState::Finished);
+		}
+	}
+}
+
+
+#[cfg(feature="user_interface")]
+pub fn draw_checkpoints(
+	checkpoints: Query<(&Checkpoint, &GlobalTransform, &Collider)>,
+	progress: Res<RaceProgress>,
+	state: Res<State<RaceState>>,
+	mut gizmos: Gizmos,
+) {
+	use bevy::color::palettes::css;
+	
+	for (checkpoint, transform, collider) in &checkpoints {
+		let Some(cuboid) = collider.shape().as_cuboid() else {continue};
+		let size = Vec3::from(cuboid.half_extents) * 2.;
+		let racing = matches!(state.get(), RaceState::Countdown | RaceState::Racing);
+		let color = if racing && checkpoint.index == progress.next_checkpoint {css::LIME} else {css::GRAY};
+		gizmos.cuboid(transform.compute_transform().with_scale(size), color);
+	}
+}
+
+
+///Formats a duration as minutes:seconds.milliseconds
+#[cfg(feature="user_interface")]
+pub fn format_time(duration: Duration) -> String {
+	let secs = duration.as_secs();
+	format!("{}:{:02}.{:03}", secs / 60, secs % 60, duration.subsec_millis())
+}
+
+
+#[cfg(feature="user_interface")]
+pub fn race_ui(
+	mut contexts: bevy_egui::EguiContexts,
+	state: Res<State<RaceState>>,
+	mut next_state: ResMut<NextState<RaceState>>,
+	mut settings: ResMut<RaceSettings>,
+	progress: Res<RaceProgress>,
+	time: Res<Time>,
+) {
+	use bevy_egui::egui;
+	let Some(ctx) = contexts.try_ctx_mut() else {
+		// Primary window is missing, because it still is being initialized or has been closed
+		// This system can still run in those conditions, so just do nothing until other systems fix it
+		return;
+	};
+	
+	let now = time.elapsed();
+	egui::Window::new("Race").resizable(false).show(ctx, |ui| {
+		match state.get() {
+			RaceState::FreeDrive => {
+				ui.add(egui::Slider::new(&mut settings.laps, 1..=10).text("Laps"));
+				if ui.button("Start race").clicked() {
+					next_state.set(RaceState::Countdown);
+				}
+			},
+			RaceState::Countdown => {
+				let left = (progress.countdown_start + settings.countdown).saturating_sub(now);
+				ui.heading(format!("{}", left.as_secs() + 1));
+			},
+			RaceState::Racing => {
+				ui.heading(format!("Lap {}/{}", progress.lap(), settings.laps));
+				ui.label(format!("Lap time: {}", format_time(now - progress.lap_start)));
+				ui.label(format!("Total time: {}", format_time(now - progress.start)));
+				for (lap, lap_time) in progress.lap_times.iter().enumerate() {
+					ui.label(format!("Lap {}: {}", lap + 1, format_time(*lap_time)));
+				}
+				if ui.button("Stop race").clicked() {
+					next_state.set(RaceState::FreeDrive);
+				}
+			},
+			RaceState::Finished => {
+				ui.heading("Finished!");
+				if let Some(total) = progress.total {
+					ui.label(format!("Total time: {}", format_time(total)));
+				}
+				for (lap, lap_time) in progress.lap_times.iter().enumerate() {
+					let best = Some(*lap_time) == progress.best_lap();
+					let text = format!("Lap {}: {}", lap + 1, format_time(*lap_time));
+					if best {
+						ui.colored_label(egui::Color32::GREEN, text);
+					} else {
+						ui.label(text);
+					}
+				}
+				ui.horizontal(|ui| {
+					if ui.button("Race again").clicked() {
+						next_state.set(RaceState::Countdown);
+					}
+					if ui.button("Free drive").clicked() {
+						next_state.set(RaceState::FreeDrive);
+					}
+				});
+			},
+		}
+	});
+}
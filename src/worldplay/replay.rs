@@ -0,0 +1,253 @@
+/*!
+Ghost replays of the best lap, to race against.
+
+Laps of the local vessel get recorded while racing, the fastest one for each track and vessel gets stored in [SAVE_DIR].
+That one then gets played back by a translucent [Ghost] that doesn't collide with anything.
+*/
+
+use std::{
+	fs,
+	path::PathBuf,
+	time::Duration,
+};
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::{element::Catalogue, track::SelectedTrack};
+
+
+///Directory replays get saved in, relative to the working directory
+pub const SAVE_DIR: &str = "replays";
+///File extension used for saved replays
+pub const EXTENSION: &str = "replay.ron";
+///Seconds between recorded samples
+pub const SAMPLE_INTERVAL: f32 = 0.05;
+
+
+///A recorded lap
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+	pub lap_time: Duration,
+	///Position and rotation of the vessel every [SAMPLE_INTERVAL] seconds since the start of the lap
+	pub samples: Vec<(Vec3, Quat)>,
+}
+
+impl Replay {
+	///Where the vessel was at the given time since the start of the lap, [None] after the end of the recording
+	pub fn transform_at(&self, time: Duration) -> Option<Transform> {
+		let index = time.as_secs_f32() / SAMPLE_INTERVAL;
+		let before = index.floor() as usize;
+		let (from, to) = (self.samples.get(before)?, self.samples.get(before + 1)?);
+		let between = index.fract();
+		Some(
+			Transform::from_translation(from.0.lerp(to.0, between))
+				.with_rotation(from.1.slerp(to.1, between))
+		)
+	}
+}
+
+
+#[derive(Debug)]
+pub enum Error {
+	Io(std::io::Error),
+	Parse(ron::error::SpannedError),
+	Serialize(ron::Error),
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io(err) => write!(f, "file error: {err}"),
+			Self::Parse(err) => write!(f, "invalid replay file: {err}"),
+			Self::Serialize(err) => write!(f, "could not serialize replay: {err}"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+
+///Path of the file the best replay of the vessel on the track gets stored in, see [SelectedTrack::key]
+pub fn path_for(track: &str, vessel_id: vessel::Id) -> PathBuf {
+	PathBuf::from(SAVE_DIR).join(format!("{track}-{}.{EXTENSION}", vessel_id.0))
+}
+
+pub fn save(track: &str, vessel_id: vessel::Id, replay: &Replay) -> Result<(), Error> {
+	let text = ron::to_string(replay).map_err(Error::Serialize)?;
+	fs::create_dir_all(SAVE_DIR).map_err(Error::Io)?;
+	fs::write(path_for(track, vessel_id), text).map_err(Error::Io)
+}
+
+///[None] if there's no replay yet
+pub fn load(track: &str, vessel_id: vessel::Id) -> Result<Option<Replay>, Error> {
+	let text = match fs::read_to_string(path_for(track, vessel_id)) {
+		Ok(text) => text,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+		Err(err) => return Err(Error::Io(err)),
+	};
+	ron::from_str(&text).map(Some).map_err(Error::Parse)
+}
+
+
+///Samples of the lap currently being driven
+#[derive(Resource, Default)]
+pub struct Recording {
+	pub samples: Vec<(Vec3, Quat)>,
+	///Time since the start of the lap, position and rotation the last time it got recorded
+	pub last: Option<(f32, Vec3, Quat)>,
+}
+
+///The fastest lap on the current track with the local vessel
+#[derive(Resource, Default)]
+pub struct BestReplay(pub Option<Replay>);
+
+///Shows where the vessel was during the [BestReplay]
+#[derive(Component)]
+pub struct Ghost;
+
+#[derive(Resource)]
+pub struct GhostMaterial(pub Handle<StandardMaterial>);
+
+impl FromWorld for GhostMaterial {
+	fn from_world(world: &mut World) -> Self {
+		let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+		Self(materials.add(StandardMaterial {
+			base_color: Color::srgba(0.8, 0.9, 1., 0.3),
+			alpha_mode: AlphaMode::Blend,
+			unlit: true,
+			..default()
+		}))
+	}
+}
+
+
+pub fn load_best_replay(
+	local: Query<&vessel::Id, With<user::LocallyControlled>>,
+	track: Res<SelectedTrack>,
+	mut best: ResMut<BestReplay>,
+) {
+	best.0 = None;
+	let Ok(vessel_id) = local.get_single() else {return};
+	match load(&track.key(), *vessel_id) {
+		Ok(replay) => best.0 = replay,
+		Err(err) => warn!(%err, "could not load best replay"),
+	}
+}
+
+
+pub fn clear_recording(
+	mut recording: ResMut<Recording>,
+) {
+	recording.samples.clear();
+	recording.last = None;
+}
+
+
+///Keeps the recording if it's a new best lap, and starts recording the next one
+pub fn finish_lap(
+	mut laps: EventReader<race::LapFinished>,
+	mut recording: ResMut<Recording>,
+	mut best: ResMut<BestReplay>,
+	local: Query<&vessel::Id, With<user::LocallyControlled>>,
+	track: Res<SelectedTrack>,
+) {
+	for lap in laps.read() {
+		let samples = std::mem::take(&mut recording.samples);
+		recording.last = None;
+		if best.0.as_ref().is_some_and(|best| best.lap_time <= lap.lap_time) {continue}
+		
+		let replay = Replay {
+			lap_time: lap.lap_time,
+			samples,
+		};
+		if let Ok(vessel_id) = local.get_single() {
+			match save(&track.key(), *vessel_id, &replay) {
+				Ok(()) => info!(lap_time=?lap.lap_time, "saved new best lap"),
+				Err(err) => error!(%err, "could not save best lap"),
+			}
+		}
+		best.0 = Some(replay);
+	}
+}
+
+
+pub fn record(
+	local: Query<(&Position, &Rotation), With<user::LocallyControlled>>,
+	mut recording: ResMut<Recording>,
+	progress: Res<race::RaceProgress>,
+	time: Res<Time>,
+) {
+	let Ok((position, rotation)) = local.get_single() else {return};
+	let lap_time = (time.elapsed() - progress.lap_start).as_secs_f32();
+	let last = recording.last.replace((lap_time, position.0, rotation.0));
+	// a sample for every interval that passed since the last one, so they stay evenly spaced in time.
+	// Frames can take longer than an interval, so the ones in between are interpolated.
+	loop {
+		let sample_time = recording.samples.len() as f32 * SAMPLE_INTERVAL;
+		if sample_time > lap_time {break}
+		let sample = match last {
+			Some((last_time, last_position, last_rotation)) if last_time < lap_time => {
+				let between = ((sample_time - last_time) / (lap_time - last_time)).clamp(0., 1.);
+				(last_position.lerp(position.0, between), last_rotation.slerp(rotation.0, between))
+			},
+			_ => (position.0, rotation.0),
+		};
+		recording.samples.push(sample);
+	}
+}
+
+
+///Spawns the ghost once there's something to show
+pub fn spawn_ghost(
+	ghosts: Query<(), With<Ghost>>,
+	best: Res<BestReplay>,
+	local: Query<&vessel::Id, With<user::LocallyControlled>>,
+	vessels: Res<Assets<vessel::SimVessel>>,
+	catalogue: Res<Catalogue>,
+	material: Res<GhostMaterial>,
+	mut cmds: Commands,
+) {
+	if !ghosts.is_empty() || best.0.is_none() {return}
+	let Some(vessel) = local.get_single().ok().and_then(|id| vessels.get(id.0)) else {return};
+	
+	cmds.spawn((
+		Ghost,
+		Name::new("Ghost"),
+		Transform::default(),
+		Visibility::Hidden,
+		StateScoped(race::RaceState::Racing),
+	)).with_children(|parent| {
+		for (elem_id, transform) in &vessel.graphics {
+			let Some(elem) = catalogue.get(elem_id) else {continue};
+			parent.spawn((
+				Mesh3d(elem.graphics.mesh.clone()),
+				MeshMaterial3d(material.0.clone()),
+				*transform,
+			));
+		}
+	});
+}
+
+
+pub fn move_ghost(
+	mut ghosts: Query<(&mut Transform, &mut Visibility), With<Ghost>>,
+	best: Res<BestReplay>,
+	progress: Res<race::RaceProgress>,
+	time: Res<Time>,
+) {
+	let lap_time = time.elapsed() - progress.lap_start;
+	let transform = best.0.as_ref().and_then(|replay| replay.transform_at(lap_time));
+	for (mut ghost_transform, mut visibility) in &mut ghosts {
+		match transform {
+			Some(transform) => {
+				*ghost_transform = transform;
+				*visibility = Visibility::Inherited;
+			},
+			// the ghost already finished this lap
+			None => *visibility = Visibility::Hidden,
+		}
+	}
+}
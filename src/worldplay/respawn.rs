@@ -0,0 +1,120 @@
+/*!
+Where vessels spawn, and putting them back there when they're stuck or fell off the track.
+*/
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::track::CurrentTrack;
+
+
+///Which of the track's spawn positions a vessel uses, so multiple vessels don't spawn inside each other
+#[derive(Component, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SpawnSlot(pub usize);
+
+impl SpawnSlot {
+	///The lowest slot not used by any of the `used` ones
+	pub fn free<'a>(used: impl IntoIterator<Item = &'a SpawnSlot>) -> Self {
+		let used = used.into_iter().map(|slot| slot.0).collect::<Vec<_>>();
+		Self((0..).find(|slot| !used.contains(slot)).expect("there should be a free slot"))
+	}
+}
+
+
+///Where the vessel should respawn instead of its [SpawnSlot], e.g. the last passed checkpoint
+#[derive(Component, Debug, Clone, Copy)]
+pub struct RespawnPoint(pub Transform);
+
+
+///Asks for the vessel on the entity to be put upright at the transform, without any velocity
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Respawn {
+	pub entity: Entity,
+	pub transform: Transform,
+}
+
+///Where a vessel should respawn
+pub fn respawn_transform(
+	slot: Option<&SpawnSlot>,
+	respawn_point: Option<&RespawnPoint>,
+	track: &CurrentTrack,
+) -> Transform {
+	match respawn_point {
+		Some(respawn_point) => respawn_point.0,
+		None => track.0.spawn_transform(slot.copied().unwrap_or_default().0),
+	}
+}
+
+
+///Only keeps the rotation around the vertical axis
+pub fn upright(rotation: Quat) -> Quat {
+	let forward = (rotation * Vec3::X).with_y(0.);
+	if forward.length_squared() < 1e-6 {
+		// pointing straight up or down, any heading will do
+		return Quat::IDENTITY;
+	}
+	Quat::from_rotation_arc(Vec3::X, forward.normalize())
+}
+
+
+pub fn respawn_vessels(
+	mut events: EventReader<Respawn>,
+	mut vessels: Query<(
+		&mut Transform,
+		&mut Position,
+		&mut Rotation,
+		&mut LinearVelocity,
+		&mut AngularVelocity,
+	)>,
+) {
+	for event in events.read() {
+		let Ok((mut transform, mut position, mut rotation, mut vel, mut rot_vel)) = vessels.get_mut(event.entity) else {
+			warn!(entity=?event.entity, "can't respawn something that isn't a spawned vessel");
+			continue;
+		};
+		let rotation_upright = upright(event.transform.rotation);
+		*transform = Transform::from_translation(event.transform.translation).with_rotation(rotation_upright);
+		position.0 = event.transform.translation;
+		rotation.0 = rotation_upright;
+		vel.0 = Vec3::ZERO;
+		rot_vel.0 = Vec3::ZERO;
+	}
+}
+
+
+///Respawns vessels that fell below the track's kill height
+pub fn kill_height(
+	vessels: Query<(Entity, &Position, Option<&SpawnSlot>, Option<&RespawnPoint>), With<vessel::VesselSpawned>>,
+	track: Res<CurrentTrack>,
+	mut respawns: EventWriter<Respawn>,
+) {
+	let height = track.0.world_kill_height();
+	for (entity, position, slot, respawn_point) in &vessels {
+		if position.y >= height {continue}
+		info!(?entity, "vessel fell off the track");
+		respawns.send(Respawn {
+			entity,
+			transform: respawn_transform(slot, respawn_point, &track),
+		});
+	}
+}
+
+
+///Resets the local vessel when pressing the reset key
+#[cfg(feature="user_interface")]
+pub fn reset_input(
+	buttons: Res<ButtonInput<KeyCode>>,
+	local: Query<(Entity, Option<&SpawnSlot>, Option<&RespawnPoint>), With<user::LocallyControlled>>,
+	track: Res<CurrentTrack>,
+	mut respawns: EventWriter<Respawn>,
+) {
+	if !buttons.just_pressed(KeyCode::KeyR) {return}
+	for (entity, slot, respawn_point) in &local {
+		respawns.send(Respawn {
+			entity,
+			transform: respawn_transform(slot, respawn_point, &track),
+		});
+	}
+}
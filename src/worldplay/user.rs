@@ -50,11 +50,17 @@ pub fn read_user_input(
 
 pub fn spawn_user(
 	mut cmds: Commands,
-	user_vessel_id: Res<UserVesselId>,
+	user_vessel_id: Option<Res<UserVesselId>>,
+	slots: Query<&respawn::SpawnSlot>,
 ) {
+	let Some(user_vessel_id) = user_vessel_id else {
+		warn!("not spawning the user, there's no vessel for it");
+		return;
+	};
 	let id = cmds.spawn((
 		LocallyControlled,
-		user_vessel_id.0
+		user_vessel_id.0,
+		respawn::SpawnSlot::free(&slots),
 	)).id();
 	
 	cmds.spawn((
@@ -99,6 +105,8 @@ pub fn update_camera(
 pub fn camera_ui(
 	mut contexts: bevy_egui::EguiContexts,
 	mut camera_settings: ResMut<CameraSettings>,
+	#[cfg(feature="user_interface")]
+	mut merge_meshes: ResMut<vessel_mesh::MergeVesselMeshes>,
 ) {
 	use bevy_egui::egui;
 	let Some(ctx) = contexts.try_ctx_mut() else {
@@ -127,5 +135,11 @@ pub fn camera_ui(
 			.smart_aim(false)
 			// .step_by((TAU/ 2_f32.powi(10)) as f64)
 		);
+		#[cfg(feature="user_interface")]
+		{
+			ui.separator();
+			ui.checkbox(&mut merge_meshes.0, "Merge vessel meshes")
+				.on_hover_text("Turn off to give every object its own entity for debugging, only affects vessels spawned afterwards");
+		}
 	});
 }
@@ -1,7 +1,7 @@
 use avian3d::prelude::*;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-use crate::editor::element::Catalogue;
+use crate::element::Catalogue;
 
 use super::*;
 
@@ -14,6 +14,24 @@ pub struct SimVessel {
 	#[reflect(ignore)]
 	pub collider: Collider,
 	pub physics_properties: VesselProperties,
+	///Calculated from the elements instead of the collider, which would give everything the same density
+	pub mass_properties: MassProperties,
+	///Parts that do something on their own
+	#[reflect(ignore)]
+	pub parts: Vec<parts::Part>,
+}
+
+impl SimVessel {
+	///Ids of the elements this vessel uses that aren't in the `catalogue`, without duplicates
+	pub fn unknown_elements<'a>(&'a self, catalogue: &Catalogue) -> Vec<&'a str> {
+		let mut unknown = Vec::new();
+		for (elem_id, _) in &self.graphics {
+			if catalogue.get(elem_id).is_none() && !unknown.contains(&elem_id.as_str()) {
+				unknown.push(elem_id.as_str());
+			}
+		}
+		unknown
+	}
 }
 
 
@@ -21,19 +39,57 @@ pub struct SimVessel {
 Unique reference to a vessel.
 When inserted on an entity, will automatically cause the corresponding vessel to be spawned/attached to the entity.
 */
-#[derive(Component, Reflect, From, Into, Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Component, Reflect, From, Into, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Id(pub uuid::Uuid);
 
+impl Id {
+	///Derived from a hash of the serialized vessel, so identical vessels get the same id.
+	/// This way they don't get stored or sent again every time they're played, by any client.
+	pub fn of(vessel: &SimVessel) -> Self {
+		let serialized = ron::to_string(vessel).expect("vessels should always be serializable");
+		let hash = blake3::hash(serialized.as_bytes());
+		let mut bytes = [0; 16];
+		bytes.copy_from_slice(&hash.as_bytes()[..16]);
+		Self(uuid::Builder::from_custom_bytes(bytes).into_uuid())
+	}
+}
+
 
 ///A marker [Component] that says this entity has a vessel spawned from it's attached [Id]
 #[derive(Component)]
 pub struct VesselSpawned;
 
 
+///How the mass of a vessel is distributed
+#[derive(Reflect, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MassProperties {
+	pub mass: f32,
+	///Relative to the vessel
+	pub center_of_mass: Vec3,
+	///Inertia tensor around the centre of mass
+	pub inertia: Mat3,
+}
+
+impl MassProperties {
+	///The components that make a rigid body use these instead of calculating them from its colliders
+	pub fn components(&self) -> impl Bundle {
+		(
+			Mass(self.mass),
+			CenterOfMass(self.center_of_mass),
+			AngularInertia::from_tensor(self.inertia),
+			NoAutoMass,
+			NoAutoCenterOfMass,
+			NoAutoAngularInertia,
+		)
+	}
+}
+
+
 ///Physical behaviour of a vessel
 #[derive(Component, Reflect, Clone, Debug, Serialize, Deserialize)]
 pub struct VesselProperties {
-	///How much forwards force to apply when the input is fully forwards
+	///How much forwards force to apply at the centre when the input is fully forwards.
+	/// Thrusters push on top of this from their own location, see [super::parts].
 	pub control_forwards_force: f32,
 	///How much torque to apply for horizontal turning when the input is fully left or right.
 	pub control_torque: f32,
@@ -43,7 +99,9 @@ pub struct VesselProperties {
 	pub rotary_friction_hor: f32,
 	///Fraction/ratio of the non-horizontal rotation speed to apply as counter-torque to reduce rotary speed.
 	pub rotary_friction_ver: f32,
-	
+	///Fraction/ratio of the speed to apply as counter-force to reduce speed.
+	pub drag: f32,
+
 }
 
 impl Default for VesselProperties {
@@ -54,6 +112,7 @@ impl Default for VesselProperties {
 			side_friction: 2.2,
 			rotary_friction_hor: 3.,
 			rotary_friction_ver: 6.,
+			drag: 0.,
 		}
 	}
 }
@@ -61,11 +120,19 @@ impl Default for VesselProperties {
 
 pub fn spawn_vessels(
 	mut cmds: Commands,
-	todo: Query<(Entity, &Id), Without<VesselSpawned>>,
+	todo: Query<(Entity, &Id, Option<&respawn::SpawnSlot>), Without<VesselSpawned>>,
+	track: Res<crate::track::CurrentTrack>,
 	vessels: Res<Assets<SimVessel>>,
+	#[cfg(feature="user_interface")]
 	elements: Res<Catalogue>,
+	#[cfg(feature="user_interface")]
+	merge: Res<super::vessel_mesh::MergeVesselMeshes>,
+	#[cfg(feature="user_interface")]
+	mut meshes: ResMut<Assets<Mesh>>,
+	#[cfg(feature="user_interface")]
+	placeholder: Res<crate::element::Placeholder>,
 ) {
-	for (entity, id) in &todo {
+	for (entity, id, slot) in &todo {
 		let Some(vessel) = vessels.get(id.0) else {
 			warn!("no vessel with asked for id {}", id.0);
 			continue;
@@ -73,27 +140,54 @@ pub fn spawn_vessels(
 		
 		debug!(vessel=?id, ?entity, "spawned vessel");
 		
-		let player = cmds.entity(entity)
+		cmds.entity(entity)
 			.insert(VesselSpawned)
 			.insert(Name::new(format!("Vessel {}",id.0)))
 			.insert(vessel.physics_properties.clone())
+			.insert(parts::Parts(vessel.parts.clone()))
+			.insert(vessel.mass_properties.components())
 			.insert(vessel.collider.clone()) // in avian3d 0.1.2 this uses an Arc under the hood so is actually rather cheap
 			.insert(Control::default())
-			.insert(Transform::default())
+			.insert(track.0.spawn_transform(slot.copied().unwrap_or_default().0))
 			.insert(Visibility::default())
 			.insert(RigidBody::Dynamic)
 			.insert(Friction::new(0.)) // extra friction is provided by the race track itself
-			.insert(StateScoped(WorldLoaded))
-			.id();
+			.insert(StateScoped(WorldLoaded));
 		
-		for (elem_id, transform) in &vessel.graphics {
-			let elem = elements.find_by_id(elem_id);
+		// the headless server only needs to simulate the vessel
+		#[cfg(feature="user_interface")]
+		{
+			let unknown = vessel.unknown_elements(&elements);
+			if !unknown.is_empty() {
+				warn!(vessel=?id, ?unknown, "vessel uses elements we don't know, showing placeholders for them");
+			}
+		}
+		#[cfg(feature="user_interface")]
+		let separate = if merge.0 {
+			let merged = super::vessel_mesh::merge_meshes(vessel, &elements, &meshes);
+			debug!(vessel=?id, meshes=merged.meshes.len(), unmerged=merged.unmerged.len(), culled_triangles=merged.culled_triangles, "merged vessel meshes");
+			for (material, mesh) in merged.meshes {
+				cmds.spawn((
+					Mesh3d(meshes.add(mesh)),
+					MeshMaterial3d(material),
+					Transform::default(),
+					Name::new("Vessel Graphic"),
+				)).set_parent(entity);
+			}
+			merged.unmerged
+		} else {
+			(0..vessel.graphics.len()).collect()
+		};
+		#[cfg(feature="user_interface")]
+		for (elem_id, transform) in separate.into_iter().map(|index| &vessel.graphics[index]) {
+			let elem = elements.get(elem_id);
+			let graphics = elem.as_ref().map_or(&placeholder.0, |elem| &elem.graphics);
 			cmds.spawn((
-				Mesh3d(elem.graphics.mesh.clone()),
-				MeshMaterial3d(elem.graphics.material.clone()),
+				Mesh3d(graphics.mesh.clone()),
+				MeshMaterial3d(graphics.material.clone()),
 				*transform,
-				Name::new("Vessel Graphic"),
-			)).set_parent(player);
+				Name::new(if elem.is_some() {"Vessel Graphic".into()} else {format!("Unknown Element {elem_id}")}),
+			)).set_parent(entity);
 		}
 	}
 }
@@ -130,6 +224,7 @@ pub fn move_vessel(
 		let side_friction = -local_vel.z * vessel.side_friction;
 		let friction = Vec3::new(0., 0., side_friction);
 		force.apply_force(tf.rotation * friction);
+		force.apply_force(-vel.0 * vessel.drag);
 		
 		let ver_rot = rot_vel.0.with_y(0.);
 		let hor_rot = rot_vel.0.with_x(0.).with_z(0.);
@@ -0,0 +1,180 @@
+/*!
+Merges the graphics of a [SimVessel] into one mesh per material, so big vessels don't need an entity and draw call per object.
+
+Faces between two objects that both completely fill their cells (see [element::Graphics::fills_cells]) can't be seen, so they get left out.
+*/
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+	asset::RenderAssetUsages,
+	prelude::*,
+	render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+};
+
+use crate::element::Catalogue;
+use super::vessel::SimVessel;
+
+
+///Whether spawned vessels get merged meshes, or a separate entity for every object.
+/// Separate entities are slower, but useful for debugging. Only affects vessels spawned afterwards.
+#[derive(Resource)]
+pub struct MergeVesselMeshes(pub bool);
+
+impl Default for MergeVesselMeshes {
+	fn default() -> Self {
+		Self(true)
+	}
+}
+
+
+///Mesh data being collected for one material
+#[derive(Default)]
+struct MeshData {
+	positions: Vec<[f32; 3]>,
+	normals: Vec<[f32; 3]>,
+	uvs: Vec<[f32; 2]>,
+	indices: Vec<u32>,
+}
+
+impl MeshData {
+	fn build(self) -> Mesh {
+		Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+			.with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+			.with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+			.with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+			.with_inserted_indices(Indices::U32(self.indices))
+	}
+}
+
+
+///The merged meshes of a vessel
+pub struct MergedMeshes {
+	pub meshes: Vec<(Handle<StandardMaterial>, Mesh)>,
+	///Indices into [SimVessel::graphics] that couldn't be merged, e.g. because their mesh isn't loaded yet.
+	/// These should be spawned as separate entities.
+	pub unmerged: Vec<usize>,
+	///How many triangles were left out because they were hidden
+	pub culled_triangles: usize,
+}
+
+
+///Cells occupied by a part with the given size (in cells) and transform relative to the vessel
+fn part_cells(size: IVec3, transform: &Transform) -> impl Iterator<Item = IVec3> {
+	let size = (transform.rotation * size.as_vec3()).abs().round().as_ivec3();
+	let min = (transform.translation - size.as_vec3() / 2.).round().as_ivec3();
+	(0..size.x).flat_map(move |x|
+		(0..size.y).flat_map(move |y|
+			(0..size.z).map(move |z| min + IVec3::new(x, y, z))
+		)
+	)
+}
+
+
+///If the triangle lies flat against the side of a cell, and all the cells on its outside are in `solid`
+fn is_hidden(triangle: [Vec3; 3], solid: &HashSet<IVec3>) -> bool {
+	let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]).normalize_or_zero();
+	let Some(axis) = (0..3).find(|axis| normal[*axis].abs() > 0.999) else {
+		return false;
+	};
+	let plane = triangle[0][axis];
+	if (plane - plane.round()).abs() > 1e-3 {
+		return false;
+	}
+	
+	let min = triangle[0].min(triangle[1]).min(triangle[2]);
+	let max = triangle[0].max(triangle[1]).max(triangle[2]);
+	// cells whose side the triangle touches, one of the axes is overwritten below
+	let from = (min + 1e-3).floor().as_ivec3();
+	let to = (max - 1e-3).ceil().as_ivec3();
+	let mut outside = from;
+	outside[axis] = if normal[axis] > 0. {plane.round() as i32} else {plane.round() as i32 - 1};
+	
+	let range = |a: usize| if a == axis {outside[a]..=outside[a]} else {from[a]..=to[a] - 1};
+	range(0).all(|x|
+		range(1).all(|y|
+			range(2).all(|z| solid.contains(&IVec3::new(x, y, z)))
+		)
+	)
+}
+
+
+///Merges the graphics of the vessel into one mesh per material
+pub fn merge_meshes(
+	vessel: &SimVessel,
+	catalogue: &Catalogue,
+	meshes: &Assets<Mesh>,
+) -> MergedMeshes {
+	let parts = vessel.graphics.iter()
+		.map(|(id, transform)| (catalogue.get(id), transform))
+		.collect::<Vec<_>>();
+	
+	let solid = parts.iter()
+		.filter_map(|(elem, transform)| elem.as_ref().map(|elem| (elem, transform)))
+		.filter(|(elem, _)| elem.graphics.fills_cells)
+		.flat_map(|(elem, transform)| part_cells(elem.size, transform))
+		.collect::<HashSet<_>>();
+	
+	let mut by_material = HashMap::<AssetId<StandardMaterial>, (Handle<StandardMaterial>, MeshData)>::new();
+	let mut unmerged = Vec::new();
+	let mut culled_triangles = 0;
+	
+	for (index, (elem, transform)) in parts.into_iter().enumerate() {
+		let Some(elem) = elem else {
+			unmerged.push(index);
+			continue;
+		};
+		let Some(mesh) = meshes.get(&elem.graphics.mesh) else {
+			unmerged.push(index);
+			continue;
+		};
+		let (
+			Some(positions),
+			Some(VertexAttributeValues::Float32x3(normals)),
+			Some(VertexAttributeValues::Float32x2(uvs)),
+		) = (
+			mesh.attribute(Mesh::ATTRIBUTE_POSITION).and_then(VertexAttributeValues::as_float3),
+			mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+			mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+		) else {
+			unmerged.push(index);
+			continue;
+		};
+		if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+			unmerged.push(index);
+			continue;
+		}
+		
+		let (_, data) = by_material.entry(elem.graphics.material.id())
+			.or_insert_with(|| (elem.graphics.material.clone(), MeshData::default()));
+		
+		let offset = data.positions.len() as u32;
+		let positions = positions.iter()
+			.map(|pos| transform.transform_point(Vec3::from_array(*pos)))
+			.collect::<Vec<_>>();
+		let indices = match mesh.indices() {
+			Some(indices) => indices.iter().collect::<Vec<_>>(),
+			None => (0..positions.len()).collect(),
+		};
+		
+		for triangle in indices.chunks_exact(3) {
+			if elem.graphics.fills_cells && is_hidden([positions[triangle[0]], positions[triangle[1]], positions[triangle[2]]], &solid) {
+				culled_triangles += 1;
+				continue;
+			}
+			data.indices.extend(triangle.iter().map(|index| offset + *index as u32));
+		}
+		data.positions.extend(positions.iter().map(|pos| pos.to_array()));
+		data.normals.extend(normals.iter().map(|normal| (transform.rotation * Vec3::from_array(*normal)).to_array()));
+		data.uvs.extend(uvs.iter().copied());
+	}
+	
+	MergedMeshes {
+		meshes: by_material.into_values()
+			.filter(|(_, data)| !data.indices.is_empty())
+			.map(|(material, data)| (material, data.build()))
+			.collect(),
+		unmerged,
+		culled_triangles,
+	}
+}